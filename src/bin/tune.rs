@@ -0,0 +1,34 @@
+// fits the evaluation weights to labelled quiet positions, one fen and result per line,
+// and writes them back out as the source of the weights the engine is built with
+use clap::Parser;
+use gegene::eval::PARAMS;
+use gegene::tune;
+
+#[derive(Parser)]
+struct Args {
+    // file of positions, each a fen followed by the result like 1-0, 0.5 or [1.0]
+    positions: String,
+    #[arg(long, default_value = "src/eval/params.rs")]
+    output: String,
+    #[arg(long, default_value_t = 500)]
+    epochs: usize,
+    #[arg(long, default_value_t = 1.0)]
+    learning_rate: f64,
+}
+
+fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+
+    let entries = tune::load(&args.positions)?;
+    println!("loaded {} positions", entries.len());
+
+    let params = tune::tune(&entries, &PARAMS, args.epochs, args.learning_rate, |epoch, error| {
+        if epoch % 10 == 0 || epoch == args.epochs {
+            println!("epoch {} error {:.8}", epoch, error);
+        }
+    });
+
+    tune::write_params(&params, &args.output)?;
+    println!("wrote {}", args.output);
+    Ok(())
+}
@@ -15,6 +15,9 @@ pub struct Bitboard(pub u64);
 // 08 09 10 11 12 13 14 15
 // 00 01 02 03 04 05 06 07
 impl Bitboard {
+    // b1, d1, ... a8, g8, the squares of the same colour as h1
+    pub const LIGHT_SQUARES: Bitboard = Bitboard(0x55_AA_55_AA_55_AA_55_AA);
+
     pub const fn from_rank_number(rank: usize) -> Bitboard {
         const RANK_TABLE: [Bitboard; 8] = [
             Bitboard(0x00_00_00_00_00_00_00_FF),
@@ -7,8 +7,9 @@ use crate::lookup::knight::KNIGHT_MOVES;
 use crate::lookup::pin_mask::PIN_MASK;
 use crate::lookup::rook_mask::ROOK_MASK;
 use crate::lookup::rook_moves::ROOK_MOVES;
+use crate::eval;
 use crate::piece::Piece;
-use crate::r#move::Square;
+use crate::r#move::{Move, MoveType, Square};
 use crate::role::Role;
 use crate::{bitboard::Bitboard, lookup::zobrist::ZOBRIST_VALUES};
 use std::fmt::{Display, Formatter};
@@ -59,6 +60,13 @@ pub struct Board {
     pub by_color: ByColor<Bitboard>,
     pub by_role: ByRole<Bitboard>,
     pub zobrist: u64,
+    // zobrist key of the pawns alone, indexes the pawn hash table
+    pub pawn_zobrist: u64,
+    // material and piece-square sums from white's point of view, kept up to date
+    // in update_bitboard so evaluating is O(1)
+    pub mg_score: i32,
+    pub eg_score: i32,
+    pub phase: i32,
 }
 
 impl Board {
@@ -74,7 +82,7 @@ impl Board {
         // . . . . . . . .
         // ♙ ♙ ♙ ♙ ♙ ♙ ♙ ♙
         // ♖ ♘ ♗ ♕ ♔ ♗ ♘ ♖
-        Board {
+        let mut board = Board {
             by_color: ByColor {
                 white: Bitboard(0x00_00_00_00_00_00_FF_FF),
                 black: Bitboard(0xFF_FF_00_00_00_00_00_00),
@@ -88,7 +96,14 @@ impl Board {
                 kings: Bitboard(0x10_00_00_00_00_00_00_10),
             },
             zobrist: 0,
-        }
+            pawn_zobrist: 0,
+            mg_score: 0,
+            eg_score: 0,
+            phase: 0,
+        };
+        (board.mg_score, board.eg_score, board.phase) = eval::psq_totals(&board);
+        board.pawn_zobrist = board.pawn_key();
+        board
     }
 
     pub fn from_fen(fen: &str) -> Board {
@@ -152,23 +167,44 @@ impl Board {
                     }
                 }
 
+                let square = index as usize + i * 8;
                 if c.is_uppercase() {
                     by_color.white |= bitboard;
-                    zobrist ^= ZOBRIST_VALUES[i * 64 + zobrist_offset];
+                    zobrist ^= ZOBRIST_VALUES[square * 12 + zobrist_offset];
                 } else {
                     by_color.black |= bitboard;
-                    zobrist ^= ZOBRIST_VALUES[i * 64 + zobrist_offset + 6];
+                    zobrist ^= ZOBRIST_VALUES[square * 12 + zobrist_offset + 6];
                 }
 
                 index += 1;
             }
         }
 
-        Board {
+        let mut board = Board {
             by_color,
             by_role,
             zobrist,
+            pawn_zobrist: 0,
+            mg_score: 0,
+            eg_score: 0,
+            phase: 0,
+        };
+        (board.mg_score, board.eg_score, board.phase) = eval::psq_totals(&board);
+        board.pawn_zobrist = board.pawn_key();
+        board
+    }
+
+    // pawn key computed from scratch, update_bitboard keeps it up to date
+    pub fn pawn_key(&self) -> u64 {
+        let mut key = 0;
+        let mut pawns = self.by_role.pawns;
+        while pawns.0 != 0 {
+            let square = pawns.0.trailing_zeros() as usize;
+            pawns.0 = pawns.0.blsr();
+            let offset = if self.by_color.white.0 & (1 << square) != 0 { 0 } else { 6 };
+            key ^= ZOBRIST_VALUES[square * 12 + offset];
         }
+        key
     }
 
     pub fn piece_at(&self, square: i32) -> Option<Piece> {
@@ -210,6 +246,7 @@ impl Board {
     #[inline]
     pub fn update_bitboard(&mut self, piece: Piece, from_square: Bitboard, to_square: Bitboard) {
         let move_bitboard = from_square | to_square;
+        self.update_psq(piece, from_square, to_square);
 
         let mut zobrist_offset = 0;
 
@@ -247,12 +284,36 @@ impl Board {
             zobrist_offset += 6;
         }
 
-        self.zobrist ^=
-            ZOBRIST_VALUES[from_square.0.trailing_zeros() as usize * 12 + zobrist_offset];
+        let mut key = ZOBRIST_VALUES[from_square.0.trailing_zeros() as usize * 12 + zobrist_offset];
         if from_square.0 != to_square.0 && to_square.0.trailing_zeros() < 64 {
-            self.zobrist ^=
-                ZOBRIST_VALUES[to_square.0.trailing_zeros() as usize * 12 + zobrist_offset];
+            key ^= ZOBRIST_VALUES[to_square.0.trailing_zeros() as usize * 12 + zobrist_offset];
+        }
+        self.zobrist ^= key;
+        if matches!(piece.role, Role::Pawn) {
+            self.pawn_zobrist ^= key;
+        }
+    }
+
+    // with equal squares the piece is added or removed, otherwise it moves
+    #[inline]
+    fn update_psq(&mut self, piece: Piece, from_square: Bitboard, to_square: Bitboard) {
+        let sign = if piece.is_white { 1 } else { -1 };
+        let from = from_square.0.trailing_zeros() as usize;
+        let (from_mg, from_eg) = eval::psq(piece, from);
+
+        if from_square != to_square {
+            let (to_mg, to_eg) = eval::psq(piece, to_square.0.trailing_zeros() as usize);
+            self.mg_score += sign * (to_mg - from_mg);
+            self.eg_score += sign * (to_eg - from_eg);
+            return;
         }
+
+        // the other colour may share the square during a capture, but never the own one
+        let removed = self.my_bitboard(piece.is_white) & from_square != Bitboard(0);
+        let sign = if removed { -sign } else { sign };
+        self.mg_score += sign * from_mg;
+        self.eg_score += sign * from_eg;
+        self.phase += if removed { -1 } else { 1 } * eval::game_phase_val(piece.role);
     }
 
     #[inline]
@@ -275,6 +336,9 @@ impl Board {
         let rank1 = Bitboard::from_rank_number(0);
         if is_white {
             (square >> 7 | square >> 9) & Bitboard(rank1.0.wrapping_shl((8 * (rank + 1)) as u32))
+        } else if rank == 0 {
+            // nothing below the first rank, a king there can't be attacked by pawns
+            Bitboard(0)
         } else {
             (square << 7 | square << 9) & Bitboard(rank1.0.wrapping_shl((8 * (rank - 1)) as u32))
         }
@@ -484,6 +548,124 @@ impl Board {
         (pin_mask_vh, pin_mask_diagonal)
     }
 
+    // pieces of both colours attacking `square` through the given occupancy
+    pub fn attackers_to(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        let square_bitboard = Bitboard(1 << square);
+        let diagonal = self.by_role.bishops | self.by_role.queens;
+        let straight = self.by_role.rooks | self.by_role.queens;
+
+        // a white pawn attacks the squares a black pawn on `square` would attack backwards
+        let white_pawns = if square >= 8 {
+            Board::pawn_attacks(false, square_bitboard) & self.by_role.pawns & self.by_color.white
+        } else {
+            Bitboard(0)
+        };
+        let black_pawns =
+            Board::pawn_attacks(true, square_bitboard) & self.by_role.pawns & self.by_color.black;
+
+        (white_pawns
+            | black_pawns
+            | KNIGHT_MOVES[square] & self.by_role.knights
+            | KING_MOVES[square] & self.by_role.kings
+            | Board::bishop_attacks(square, occupied) & diagonal
+            | Board::rook_attacks(square, occupied) & straight)
+            & occupied
+    }
+
+    // the least valuable piece in `attackers`, bishops are worth more than knights
+    fn least_valuable(&self, attackers: Bitboard) -> Option<(Role, Bitboard)> {
+        for (role, pieces) in [
+            (Role::Pawn, self.by_role.pawns),
+            (Role::Knight, self.by_role.knights),
+            (Role::Bishop, self.by_role.bishops),
+            (Role::Rook, self.by_role.rooks),
+            (Role::Queen, self.by_role.queens),
+            (Role::King, self.by_role.kings),
+        ] {
+            let candidates = attackers & pieces;
+            if candidates != Bitboard(0) {
+                return Some((role, Bitboard(candidates.0.blsi())));
+            }
+        }
+        None
+    }
+
+    // static exchange evaluation: the material balance of the capture sequence on the
+    // target square, both sides always recapturing with their least valuable piece
+    pub fn see(&self, played_move: Move) -> i32 {
+        let to = played_move.to().0 as usize;
+        let mut occupied = (self.by_color.white | self.by_color.black)
+            ^ Bitboard(1 << played_move.from().0);
+
+        let mut gain = [0; 32];
+        let mut piece_value = played_move.role().value();
+        match played_move.move_type() {
+            MoveType::EnPassant => {
+                let captured = if played_move.is_white() { to - 8 } else { to + 8 };
+                occupied ^= Bitboard(1 << captured);
+                gain[0] = Role::Pawn.value();
+            }
+            MoveType::Promotion => {
+                piece_value = played_move.promotion_role().role().value();
+                gain[0] = piece_value - Role::Pawn.value();
+                if played_move.is_capture() {
+                    gain[0] += played_move.capture_role().value();
+                }
+            }
+            _ if played_move.is_capture() => gain[0] = played_move.capture_role().value(),
+            _ => {}
+        }
+
+        let diagonal = self.by_role.bishops | self.by_role.queens;
+        let straight = self.by_role.rooks | self.by_role.queens;
+        let mut attackers = self.attackers_to(to, occupied);
+        let mut is_white = !played_move.is_white();
+        let mut depth = 0;
+
+        while depth < gain.len() - 1 {
+            attackers &= occupied;
+            let Some((role, attacker)) = self.least_valuable(attackers & self.my_bitboard(is_white))
+            else {
+                break;
+            };
+            // the king may only take if nothing can take back
+            if matches!(role, Role::King) && attackers & self.enemy_bitboard(is_white) != Bitboard(0)
+            {
+                break;
+            }
+
+            depth += 1;
+            gain[depth] = piece_value - gain[depth - 1];
+            // neither side can improve by going on, this capture won't be made
+            if (-gain[depth - 1]).max(gain[depth]) < 0 {
+                depth -= 1;
+                break;
+            }
+
+            // x-rays: sliders behind the capturing piece join in
+            occupied ^= attacker;
+            if matches!(role, Role::Pawn | Role::Bishop | Role::Queen) {
+                attackers |= Board::bishop_attacks(to, occupied) & diagonal;
+            }
+            if matches!(role, Role::Rook | Role::Queen) {
+                attackers |= Board::rook_attacks(to, occupied) & straight;
+            }
+
+            piece_value = role.value();
+            is_white = !is_white;
+        }
+
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+
+    pub fn see_ge(&self, played_move: Move, threshold: i32) -> bool {
+        self.see(played_move) >= threshold
+    }
+
     pub const fn my_bitboard(self, is_white: bool) -> Bitboard {
         if is_white {
             self.by_color.white
@@ -600,3 +782,76 @@ impl Display for Board {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::r#move::Move;
+    use crate::values::*;
+
+    fn find_move(game: &Game, uci: &str) -> Move {
+        let (moves, count) = game.get_legal_moves();
+        moves[0..count]
+            .iter()
+            .copied()
+            .find(|m| m.to_algebraic() == uci)
+            .expect("move not legal")
+    }
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let game = Game::from_fen(fen).expect("invalid FEN");
+        game.board.see(find_move(&game, uci))
+    }
+
+    #[test]
+    fn see_undefended() {
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            PAWN_VALUE
+        );
+    }
+
+    #[test]
+    fn see_with_x_rays() {
+        // Nxe5 Nxe5 Rxe5 Bxe5 Qxe5 Qxe5, the rook and queen behind each other join in
+        assert_eq!(
+            see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"),
+            PAWN_VALUE - KNIGHT_VALUE
+        );
+    }
+
+    #[test]
+    fn see_king_only_takes_undefended_pieces() {
+        // Bxe3 Rxe3, Kxe3 is illegal because of the king on f2
+        assert_eq!(
+            see("8/8/8/6b1/8/3kP3/5K2/4R3 b - - 0 1", "g5e3"),
+            PAWN_VALUE - BISHOP_VALUE
+        );
+        // without it the king takes back
+        assert_eq!(see("8/8/8/6b1/8/3kP3/8/4R2K b - - 0 1", "g5e3"), PAWN_VALUE);
+    }
+
+    #[test]
+    fn see_en_passant_and_promotion() {
+        assert_eq!(
+            see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"),
+            PAWN_VALUE
+        );
+        assert_eq!(
+            see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"),
+            -PAWN_VALUE
+        );
+        assert_eq!(
+            see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"),
+            ROOK_VALUE + QUEEN_VALUE - PAWN_VALUE
+        );
+    }
+
+    #[test]
+    fn see_ge() {
+        let game = Game::from_fen("7k/8/4p3/3p4/8/8/8/K2Q4 w - - 0 1").expect("invalid FEN");
+        let capture = find_move(&game, "d1d5");
+        assert!(game.board.see_ge(capture, PAWN_VALUE - QUEEN_VALUE));
+        assert!(!game.board.see_ge(capture, 0));
+    }
+}
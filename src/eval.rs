@@ -0,0 +1,597 @@
+use crate::bitboard::Bitboard;
+use crate::bmi::Bmi;
+use crate::board::Board;
+use crate::lookup::king::KING_MOVES;
+use crate::lookup::knight::KNIGHT_MOVES;
+use crate::pawns::{self, PawnTable};
+use crate::piece::Piece;
+use crate::role::Role;
+use crate::values::*;
+pub use params::PARAMS;
+use std::fmt::{Display, Formatter};
+
+mod params;
+
+// phase of the starting position, the middlegame weight when tapering
+pub const MAX_PHASE: i32 = 24;
+
+pub fn game_phase_val(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 0,
+        Role::Knight => 1,
+        Role::Bishop => 1,
+        Role::Rook => 2,
+        Role::Queen => 4,
+        Role::King => 0,
+    }
+}
+
+// every weight of the evaluation as (middlegame, endgame). The values in use are
+// generated into params.rs by the tuner, everything indexed by role follows `Role`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    pub piece_values: [(i32, i32); 6],
+    // piece-square tables as seen from white, the first row is the eighth rank
+    pub tables: [[[(i32, i32); 8]; 8]; 6],
+    // indexed by the rank relative to the pawn's side
+    pub passed_pawn: [(i32, i32); 8],
+    pub connected_pawn: [(i32, i32); 8],
+    pub doubled_pawn: (i32, i32),
+    pub isolated_pawn: (i32, i32),
+    pub backward_pawn: (i32, i32),
+    // per attacked square in the mobility area, counted from MOBILITY_BASE
+    pub mobility: [(i32, i32); 6],
+    // own pawns one and two ranks in front of the king
+    pub pawn_shield: [(i32, i32); 2],
+    pub king_semi_open_file: (i32, i32),
+    pub king_open_file: (i32, i32),
+    // attack units per square of the king zone a piece attacks
+    pub king_attack_weight: [(i32, i32); 6],
+}
+
+impl EvalParams {
+    // all weights in declaration order, the tables row by row
+    pub fn weights_mut(&mut self) -> Vec<&mut (i32, i32)> {
+        let mut weights: Vec<&mut (i32, i32)> = Vec::new();
+        weights.extend(self.piece_values.iter_mut());
+        weights.extend(self.tables.iter_mut().flatten().flatten());
+        weights.extend(self.passed_pawn.iter_mut());
+        weights.extend(self.connected_pawn.iter_mut());
+        weights.push(&mut self.doubled_pawn);
+        weights.push(&mut self.isolated_pawn);
+        weights.push(&mut self.backward_pawn);
+        weights.extend(self.mobility.iter_mut());
+        weights.extend(self.pawn_shield.iter_mut());
+        weights.push(&mut self.king_semi_open_file);
+        weights.push(&mut self.king_open_file);
+        weights.extend(self.king_attack_weight.iter_mut());
+        weights
+    }
+}
+
+pub fn piece_value(role: Role) -> (i32, i32) {
+    PARAMS.piece_values[role as usize]
+}
+
+pub fn table(role: Role) -> &'static [[(i32, i32); 8]; 8] {
+    &PARAMS.tables[role as usize]
+}
+
+// the tables are written as seen from white, the first row is the eighth rank
+pub fn table_index(is_white: bool, square: usize) -> (usize, usize) {
+    let rank = square / 8;
+    let file = square % 8;
+    if is_white {
+        (7 - rank, file)
+    } else {
+        (rank, file)
+    }
+}
+
+// material and piece-square value of a piece, from its own side's point of view
+pub fn psq(piece: Piece, square: usize) -> (i32, i32) {
+    let (row, file) = table_index(piece.is_white, square);
+    let (mg, eg) = piece_value(piece.role);
+    let (table_mg, table_eg) = table(piece.role)[row][file];
+    (mg + table_mg, eg + table_eg)
+}
+
+// middlegame and endgame score from white's point of view and the phase,
+// computed from scratch. The board keeps these up to date incrementally.
+pub fn psq_totals(board: &Board) -> (i32, i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    let mut phase = 0;
+
+    for square in 0..64 {
+        let Some(piece) = board.piece_at(square) else {
+            continue;
+        };
+        let (piece_mg, piece_eg) = psq(piece, square as usize);
+        let sign = if piece.is_white { 1 } else { -1 };
+        mg += sign * piece_mg;
+        eg += sign * piece_eg;
+        phase += game_phase_val(piece.role);
+    }
+
+    (mg, eg, phase)
+}
+
+// squares attacked by a set of pawns
+fn pawn_attacks(pawns: Bitboard, is_white: bool) -> Bitboard {
+    let not_a_file = !Bitboard::from_file_number(0).0;
+    let not_h_file = !Bitboard::from_file_number(7).0;
+    if is_white {
+        Bitboard(((pawns.0 & not_a_file) << 7) | ((pawns.0 & not_h_file) << 9))
+    } else {
+        Bitboard(((pawns.0 & not_h_file) >> 7) | ((pawns.0 & not_a_file) >> 9))
+    }
+}
+
+fn dot(counts: &[i32], weights: &[(i32, i32)]) -> (i32, i32) {
+    counts
+        .iter()
+        .zip(weights)
+        .fold((0, 0), |(mg, eg), (count, weight)| (mg + count * weight.0, eg + count * weight.1))
+}
+
+// what the pieces of one side attack, collected in a single pass over them
+pub struct Activity {
+    // attacked squares of the mobility area above MOBILITY_BASE, by role
+    pub mobility: [i32; 6],
+    // attacked squares of the enemy king zone by role, and the number of pieces attacking it
+    pub king_attacks: [i32; 6],
+    pub attackers: usize,
+}
+
+impl Activity {
+    pub fn mobility_score(&self) -> (i32, i32) {
+        dot(&self.mobility, &PARAMS.mobility)
+    }
+}
+
+pub fn activity(board: &Board, is_white: bool) -> Activity {
+    let own = board.my_bitboard(is_white);
+    let enemy = board.enemy_bitboard(is_white);
+    let occupied = own | enemy;
+    let enemy_king = (board.by_role.kings & enemy).0.trailing_zeros() as usize;
+    let king_zone = if enemy_king < 64 {
+        KING_MOVES[enemy_king] | Bitboard(1 << enemy_king)
+    } else {
+        Bitboard(0)
+    };
+    // squares defended by enemy pawns are not worth counting
+    let area = !own & !pawn_attacks(board.by_role.pawns & enemy, !is_white);
+
+    let mut activity = Activity {
+        mobility: [0; 6],
+        king_attacks: [0; 6],
+        attackers: 0,
+    };
+
+    for role in [Role::Knight, Role::Bishop, Role::Rook, Role::Queen] {
+        let mut pieces = own
+            & match role {
+                Role::Knight => board.by_role.knights,
+                Role::Bishop => board.by_role.bishops,
+                Role::Rook => board.by_role.rooks,
+                _ => board.by_role.queens,
+            };
+        while pieces.0 != 0 {
+            let square = pieces.0.trailing_zeros() as usize;
+            pieces.0 = pieces.0.blsr();
+
+            let attacks = match role {
+                Role::Knight => KNIGHT_MOVES[square],
+                Role::Bishop => Board::bishop_attacks(square, occupied),
+                Role::Rook => Board::rook_attacks(square, occupied),
+                _ => Board::bishop_attacks(square, occupied) | Board::rook_attacks(square, occupied),
+            };
+
+            activity.mobility[role as usize] +=
+                (attacks & area).0.count_ones() as i32 - MOBILITY_BASE[role as usize];
+
+            let hits = (attacks & king_zone).0.count_ones() as i32;
+            if hits > 0 {
+                activity.king_attacks[role as usize] += hits;
+                activity.attackers += 1;
+            }
+        }
+    }
+
+    activity
+}
+
+// own pawns one and two ranks in front of the king, and the files next to it without them
+#[derive(Debug, Default)]
+pub struct KingShelter {
+    pub shield: [i32; 2],
+    pub semi_open_files: i32,
+    pub open_files: i32,
+}
+
+pub fn king_shelter(board: &Board, is_white: bool) -> KingShelter {
+    let mut shelter = KingShelter::default();
+    let king = (board.by_role.kings & board.my_bitboard(is_white)).0.trailing_zeros() as usize;
+    if king > 63 {
+        return shelter;
+    }
+    let own_pawns = board.by_role.pawns & board.my_bitboard(is_white);
+    let king_file = (king % 8) as i32;
+    let king_rank = (king / 8) as i32;
+    let forward = if is_white { 1 } else { -1 };
+
+    for file in (king_file - 1).max(0)..=(king_file + 1).min(7) {
+        for distance in 0..2 {
+            let rank = king_rank + forward * (distance as i32 + 1);
+            if (0..8).contains(&rank) && own_pawns.0 & (1 << (rank * 8 + file)) != 0 {
+                shelter.shield[distance] += 1;
+            }
+        }
+
+        let file_mask = Bitboard::from_file_number(file as usize);
+        if own_pawns & file_mask == Bitboard(0) {
+            shelter.semi_open_files += 1;
+            if board.by_role.pawns & file_mask == Bitboard(0) {
+                shelter.open_files += 1;
+            }
+        }
+    }
+
+    shelter
+}
+
+// pawn shield, open files next to the king and the attacks of the enemy on its zone
+pub fn king_safety(board: &Board, is_white: bool, enemy: &Activity) -> (i32, i32) {
+    let shelter = king_shelter(board, is_white);
+    let (mut mg, mut eg) = dot(&shelter.shield, &PARAMS.pawn_shield);
+    mg += shelter.semi_open_files * PARAMS.king_semi_open_file.0
+        + shelter.open_files * PARAMS.king_open_file.0;
+    eg += shelter.semi_open_files * PARAMS.king_semi_open_file.1
+        + shelter.open_files * PARAMS.king_open_file.1;
+
+    // a lone attacker is rarely dangerous, several of them are
+    let (units_mg, units_eg) = dot(&enemy.king_attacks, &PARAMS.king_attack_weight);
+    let scale = KING_ATTACK_SCALE[enemy.attackers.min(7)];
+    mg -= units_mg * scale / 100;
+    eg -= units_eg * scale / 100;
+
+    (mg, eg)
+}
+
+fn taper(phase: i32, mg: i32, eg: i32) -> i32 {
+    let mg_phase = phase.min(MAX_PHASE);
+    let eg_phase = MAX_PHASE - mg_phase;
+    (mg * mg_phase + eg * eg_phase) / MAX_PHASE
+}
+
+// the endgame score counts this many 64ths of itself, less when the side ahead can hardly win
+pub const SCALE_NORMAL: i32 = 64;
+// bishops of opposite colours with nothing but pawns besides, and with more pieces on the board
+const SCALE_OPPOSITE_BISHOPS: i32 = 22;
+const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 46;
+
+fn non_pawn_material(board: &Board, side: Bitboard) -> i32 {
+    let roles = &board.by_role;
+    [
+        (roles.bishops, Role::Bishop),
+        (roles.knights, Role::Knight),
+        (roles.rooks, Role::Rook),
+        (roles.queens, Role::Queen),
+    ]
+    .into_iter()
+    .map(|(pieces, role)| (pieces & side).0.count_ones() as i32 * piece_value(role).0)
+    .sum()
+}
+
+// how much of its endgame advantage the side `is_white` can hope to convert, in 64ths.
+// Without pawns a minor piece more rarely wins, a bishop that doesn't control the
+// queening square of a rook pawn can't drive the king out of the corner, and bishops
+// of opposite colours hold against extra pawns.
+pub fn scale_factor(board: &Board, is_white: bool) -> i32 {
+    let roles = &board.by_role;
+    let strong = board.my_bitboard(is_white);
+    let weak = board.enemy_bitboard(is_white);
+    let strong_pawns = roles.pawns & strong;
+    let strong_material = non_pawn_material(board, strong);
+    let weak_material = non_pawn_material(board, weak);
+    let bishop = piece_value(Role::Bishop).0;
+    let pieces = strong & !roles.kings & !roles.pawns;
+
+    if strong_pawns == Bitboard(0) {
+        // two knights can't force mate a bare king, with enemy pawns left they often can
+        if pieces == strong & roles.knights
+            && pieces.0.count_ones() <= 2
+            && roles.pawns & weak == Bitboard(0)
+        {
+            return 0;
+        }
+        if strong_material - weak_material <= bishop {
+            return if strong_material < piece_value(Role::Rook).0 {
+                0
+            } else if weak_material <= bishop {
+                4
+            } else {
+                14
+            };
+        }
+    }
+
+    let weak_king = (roles.kings & weak).0.trailing_zeros() as usize;
+    for file in [0, 7] {
+        let on_file = Bitboard::from_file_number(file);
+        if strong_pawns == Bitboard(0) || strong_pawns & !on_file != Bitboard(0) || weak_king >= 64 {
+            continue;
+        }
+        let queening = if is_white { 56 + file } else { file };
+        let queening_colour = if Bitboard(1 << queening) & Bitboard::LIGHT_SQUARES != Bitboard(0) {
+            Bitboard::LIGHT_SQUARES
+        } else {
+            !Bitboard::LIGHT_SQUARES
+        };
+        let distance = (weak_king / 8).abs_diff(queening / 8).max((weak_king % 8).abs_diff(file));
+        if pieces & !roles.bishops == Bitboard(0) && pieces & queening_colour == Bitboard(0) && distance <= 1 {
+            return 0;
+        }
+    }
+
+    let strong_bishops = roles.bishops & strong;
+    let weak_bishops = roles.bishops & weak;
+    if strong_bishops.0.count_ones() == 1
+        && weak_bishops.0.count_ones() == 1
+        && (strong_bishops & Bitboard::LIGHT_SQUARES == Bitboard(0))
+            != (weak_bishops & Bitboard::LIGHT_SQUARES == Bitboard(0))
+    {
+        return if strong_material == bishop && weak_material == bishop {
+            SCALE_OPPOSITE_BISHOPS
+        } else {
+            SCALE_OPPOSITE_BISHOPS_WITH_PIECES
+        };
+    }
+
+    SCALE_NORMAL
+}
+
+// tapered between the middlegame and endgame score, from the side to move's point of view
+pub fn evaluate(board: &Board, is_white: bool, pawn_table: &mut PawnTable) -> i32 {
+    let (pawns_mg, pawns_eg) = pawn_table.probe(board);
+    let white = activity(board, true);
+    let black = activity(board, false);
+    let (white_king_mg, white_king_eg) = king_safety(board, true, &black);
+    let (black_king_mg, black_king_eg) = king_safety(board, false, &white);
+
+    let (white_mobility, black_mobility) = (white.mobility_score(), black.mobility_score());
+
+    let mg = board.mg_score
+        + pawns_mg
+        + white_mobility.0 - black_mobility.0
+        + white_king_mg - black_king_mg;
+    let eg = board.eg_score
+        + pawns_eg
+        + white_mobility.1 - black_mobility.1
+        + white_king_eg - black_king_eg;
+
+    let scale = scale_factor(board, eg > 0);
+    let score = taper(board.phase, mg, eg * scale / SCALE_NORMAL);
+    if is_white {
+        score
+    } else {
+        -score
+    }
+}
+
+// every term of the evaluation as (mg, eg) for white and black, computed from scratch
+pub struct Trace {
+    pub terms: Vec<(&'static str, [(i32, i32); 2])>,
+    pub phase: i32,
+    // of the endgame score, in 64ths
+    pub scale: i32,
+}
+
+impl Trace {
+    // middlegame and endgame sum from white's point of view
+    pub fn total(&self) -> (i32, i32) {
+        self.terms.iter().fold((0, 0), |(mg, eg), (_, [white, black])| {
+            (mg + white.0 - black.0, eg + white.1 - black.1)
+        })
+    }
+
+    // the tapered score from white's point of view, the same as `evaluate` for white
+    pub fn blended(&self) -> i32 {
+        let (mg, eg) = self.total();
+        taper(self.phase, mg, eg * self.scale / SCALE_NORMAL)
+    }
+}
+
+pub fn trace(board: &Board) -> Trace {
+    let mut material = [(0, 0); 2];
+    let mut tables = [(0, 0); 2];
+    for square in 0..64 {
+        let Some(piece) = board.piece_at(square) else {
+            continue;
+        };
+        let side = if piece.is_white { 0 } else { 1 };
+        let (mg, eg) = psq(piece, square as usize);
+        let (material_mg, material_eg) = piece_value(piece.role);
+        material[side].0 += material_mg;
+        material[side].1 += material_eg;
+        tables[side].0 += mg - material_mg;
+        tables[side].1 += eg - material_eg;
+    }
+
+    let white = activity(board, true);
+    let black = activity(board, false);
+
+    let mut trace = Trace {
+        terms: vec![
+            ("Material", material),
+            ("PST", tables),
+            ("Pawns", [pawns::evaluate_side(board, true), pawns::evaluate_side(board, false)]),
+            ("Mobility", [white.mobility_score(), black.mobility_score()]),
+            (
+                "King safety",
+                [king_safety(board, true, &black), king_safety(board, false, &white)],
+            ),
+        ],
+        phase: board.phase,
+        scale: SCALE_NORMAL,
+    };
+    trace.scale = scale_factor(board, trace.total().1 > 0);
+    trace
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let separator = "-------------+-------------+-------------+-------------";
+        writeln!(f, "        Term |    White    |    Black    |    Total")?;
+        writeln!(f, "             |   MG    EG  |   MG    EG  |   MG    EG")?;
+        writeln!(f, "{}", separator)?;
+        for (name, [white, black]) in &self.terms {
+            writeln!(
+                f,
+                "{:>12} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+                name,
+                white.0,
+                white.1,
+                black.0,
+                black.1,
+                white.0 - black.0,
+                white.1 - black.1
+            )?;
+        }
+        writeln!(f, "{}", separator)?;
+        let (mg, eg) = self.total();
+        writeln!(f, "{:>12} | {:>11} | {:>11} | {:>5} {:>5}", "Total", "", "", mg, eg)?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {} / {}", self.phase.min(MAX_PHASE), MAX_PHASE)?;
+        writeln!(f, "Endgame scale: {} / {}", self.scale, SCALE_NORMAL)?;
+        write!(f, "Classical evaluation: {} cp (white side)", self.blended())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn pawn_tables_are_mirrored() {
+        let white = psq(
+            Piece {
+                is_white: true,
+                role: Role::Pawn,
+            },
+            52, // e7
+        );
+        let black = psq(
+            Piece {
+                is_white: false,
+                role: Role::Pawn,
+            },
+            12, // e2
+        );
+        assert_eq!(white, black);
+        assert_eq!(white.0, piece_value(Role::Pawn).0 + PARAMS.tables[Role::Pawn as usize][1][4].0);
+    }
+
+    #[test]
+    fn symmetric_positions_are_equal() {
+        let white = Game::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .expect("invalid fen");
+        let black = Game::from_fen("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4")
+            .expect("invalid fen");
+        let mut pawn_table = PawnTable::new();
+        assert_eq!(
+            evaluate(&white.board, white.is_white, &mut pawn_table),
+            evaluate(&black.board, black.is_white, &mut pawn_table)
+        );
+    }
+
+    #[test]
+    fn mobility_counts_free_squares() {
+        // a knight in the corner against one in the centre
+        let corner = Board::from_fen("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        let centre = Board::from_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(activity(&corner, true).mobility_score().0, (2 - 4) * PARAMS.mobility[Role::Knight as usize].0);
+        assert_eq!(activity(&centre, true).mobility_score().0, (8 - 4) * PARAMS.mobility[Role::Knight as usize].0);
+
+        // squares guarded by enemy pawns don't count
+        let guarded = Board::from_fen("4k3/8/2p1p3/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(activity(&guarded, true).mobility_score().0, (6 - 4) * PARAMS.mobility[Role::Knight as usize].0);
+    }
+
+    #[test]
+    fn castled_king_is_safer_than_central_king() {
+        let castled = Board::from_fen("r1bq1rk1/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQ1RK1 w - - 0 1");
+        let central = Board::from_fen("r1bq1rk1/pppp1ppp/2n2n2/4p3/4P3/2NK1N2/PPPP1PPP/R1BQ1R2 w - - 0 1");
+        let castled = king_safety(&castled, true, &activity(&castled, false)).0;
+        let central = king_safety(&central, true, &activity(&central, false)).0;
+        assert!(castled > central, "{} <= {}", castled, central);
+    }
+
+    #[test]
+    fn attackers_on_the_king_zone() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/3RQ1K1 b - - 0 1");
+        let alone = activity(&board, true);
+        assert_eq!(alone.attackers, 0);
+
+        let board = Board::from_fen("6k1/5ppp/8/7q/8/8/5PPP/3r2K1 w - - 0 1");
+        let black = activity(&board, false);
+        assert_eq!(black.attackers, 2);
+        let (attacked, _) = king_safety(&board, true, &black);
+        let (quiet, _) = king_safety(&board, true, &activity(&board, true));
+        assert!(attacked < quiet);
+    }
+
+    #[test]
+    fn drawish_endgames_are_scaled_down() {
+        for (fen, scale) in [
+            // a knight, two knights and the exchange without pawns, two knights against a pawn
+            ("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1", 0),
+            ("8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1", 0),
+            ("8/8/4k3/8/4p3/3K4/8/5NN1 w - - 0 1", SCALE_NORMAL),
+            ("8/8/4k3/8/2b5/3K4/8/6R1 w - - 0 1", 4),
+            ("8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1", SCALE_NORMAL),
+            // the wrong bishop, and the right one
+            ("7k/8/8/8/8/3K3P/8/5B2 w - - 0 1", 0),
+            ("7k/8/8/8/8/3K3P/8/4B3 w - - 0 1", SCALE_NORMAL),
+            ("8/8/8/8/8/3K3P/k7/5B2 w - - 0 1", SCALE_NORMAL),
+            // bishops of opposite colours, alone and with rooks
+            ("8/4bk2/8/2P5/1P6/3K4/8/5B2 w - - 0 1", SCALE_OPPOSITE_BISHOPS),
+            ("r7/4bk2/8/2P5/1P6/3K4/8/R4B2 w - - 0 1", SCALE_OPPOSITE_BISHOPS_WITH_PIECES),
+            ("8/5k2/4b3/2P5/1P6/3K4/8/5B2 w - - 0 1", SCALE_NORMAL),
+        ] {
+            assert_eq!(scale_factor(&Board::from_fen(fen), true), scale, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn trace_adds_up_to_evaluate() {
+        let mut pawn_table = PawnTable::new();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bq1rk1/pppp1ppp/2n2n2/4p3/4P3/2NK1N2/PPPP1PPP/R1BQ1R2 b - - 0 1",
+            "8/5pk1/6p1/3P4/1p6/8/5PPP/6K1 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen);
+            let trace = trace(&board);
+            assert_eq!(trace.blended(), evaluate(&board, true, &mut pawn_table), "{}", fen);
+            assert_eq!(trace.terms.len(), 5);
+        }
+    }
+
+    #[test]
+    fn incremental_matches_from_scratch() {
+        let mut game = Game::from_fen("r3k2r/1P6/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1").expect("invalid fen");
+        // en passant, promotions, castling and captures
+        for uci in ["e2e4", "d4e3", "b7a8n", "e8g8", "e1c1", "e3e2", "d1d8", "f8d8", "h1h2", "e2e1q"] {
+            game.play_uci(uci).expect("illegal move");
+            assert_eq!(
+                (game.board.mg_score, game.board.eg_score, game.board.phase),
+                psq_totals(&game.board),
+                "after {}",
+                uci
+            );
+        }
+    }
+}
@@ -2,6 +2,9 @@ use crate::bitboard::Bitboard;
 use crate::bmi::Bmi;
 use crate::lookup::king::KING_MOVES;
 use crate::lookup::knight::KNIGHT_MOVES;
+use crate::lookup::zobrist::{ZOBRIST_CASTLING, ZOBRIST_EN_PASSANT, ZOBRIST_SIDE};
+#[cfg(feature = "nnue")]
+use crate::nnue;
 use crate::piece::Piece;
 use crate::r#move::MoveType;
 use crate::role::{PromotionRole, Role};
@@ -33,7 +36,19 @@ pub enum Outcome {
     Draw(DrawType),
 }
 
+// everything a move changes besides the pieces, so it can be taken back
 #[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    played_move: Move,
+    captured: Option<Role>,
+    white_castling_rights: CastlingRight,
+    black_castling_rights: CastlingRight,
+    en_passant_target: Option<Square>,
+    halfmove_clock: i32,
+    zobrist: u64,
+}
+
+#[derive(Debug, Clone)]
 pub struct Game {
     pub board: Board,
     pub is_white: bool,
@@ -42,24 +57,32 @@ pub struct Game {
     pub en_passant_target: Option<Square>,
     pub halfmove_clock: i32,
     pub fullmoves: i32,
-    pub outcome: Outcome,
-    pub history: ([u64; 200], usize),
+    // zobrist keys of every position so far, the current one last
+    pub history: Vec<u64>,
+    // one entry per move played, the last one on top
+    undo: Vec<Undo>,
+    #[cfg(feature = "nnue")]
+    pub nnue: nnue::Accumulators,
+    // the accumulators from before every move on the undo stack that isn't a null move
+    #[cfg(feature = "nnue")]
+    nnue_undo: Vec<nnue::Accumulators>,
 }
 
 impl Game {
-    pub fn perft(&self, depth: u32, current_depth: u32, debug: bool) -> u64 {
-        if current_depth == 0 {
+    // leaf nodes `depth` plies down, with `debug` the count below each root move is printed
+    pub fn perft(&mut self, depth: u32, debug: bool) -> u64 {
+        if depth == 0 {
             return 1;
         }
 
         let mut nodes = 0;
         let (legal_moves, count) = self.get_legal_moves();
-        for i in 0..count {
-            let mut game = *self;
-            game.play(legal_moves[i]);
-            let child_nodes = game.perft(depth, current_depth - 1, debug);
-            if current_depth == depth && debug {
-                println!("{}: {}", legal_moves[i], child_nodes);
+        for &m in &legal_moves[0..count] {
+            self.make_move(m);
+            let child_nodes = self.perft(depth - 1, false);
+            self.unmake_move();
+            if debug {
+                println!("{}: {}", m, child_nodes);
             }
             nodes += child_nodes;
         }
@@ -67,7 +90,20 @@ impl Game {
         nodes
     }
 
-    pub fn get_legal_moves(self) -> ([Move; 218], usize) {
+    pub fn get_legal_moves(&self) -> ([Move; 218], usize) {
+        self.generate_moves(false)
+    }
+
+    // captures, en passant and promotions only, for the quiescence search
+    pub fn get_legal_captures(&self) -> ([Move; 218], usize) {
+        self.generate_moves(true)
+    }
+
+    pub fn in_check(&self) -> bool {
+        self.board.check_mask(self.is_white).1 != Bitboard(u64::MAX)
+    }
+
+    fn generate_moves(&self, captures_only: bool) -> ([Move; 218], usize) {
         let mut index = 0;
         let mut moves: [Move; 218] = [Move::null(); 218];
 
@@ -90,7 +126,7 @@ impl Game {
             let king_square = king_bitboard.0.trailing_zeros() as usize;
             let mut king_moves = KING_MOVES[king_square];
 
-            king_moves &= enemy_or_empty;
+            king_moves &= if captures_only { enemy_bitboard } else { enemy_or_empty };
             king_moves &= !seen_by_enemy;
 
             for i in 0..64 {
@@ -216,6 +252,13 @@ impl Game {
             };
 
             moves_bitboard &= enemy_or_empty;
+            if captures_only {
+                moves_bitboard &= if is_promotion {
+                    enemy_bitboard | Bitboard::from_rank_number(0) | Bitboard::from_rank_number(7)
+                } else {
+                    enemy_bitboard
+                };
+            }
             if is_pinned_vh {
                 moves_bitboard &= pin_mask_vh;
             }
@@ -307,7 +350,8 @@ impl Game {
 
         let queenside_extension =
             Bitboard(2 * (self.is_white as u64) + 144115188075855872 * (!self.is_white as u64));
-        if self.white_castling_rights.king_side
+        if !captures_only
+            && self.white_castling_rights.king_side
             && kingside_castle_without_king
                 & (self.board.by_color.black | self.board.by_color.white)
                 == Bitboard(0)
@@ -328,7 +372,8 @@ impl Game {
             index += 1;
         }
 
-        if self.white_castling_rights.queen_side
+        if !captures_only
+            && self.white_castling_rights.queen_side
             && queenside_castle_mask_without_king
                 & (self.board.by_color.black | self.board.by_color.white)
                 == Bitboard(0)
@@ -351,7 +396,8 @@ impl Game {
             index += 1;
         }
 
-        if self.black_castling_rights.king_side
+        if !captures_only
+            && self.black_castling_rights.king_side
             && kingside_castle_without_king
                 & (self.board.by_color.black | self.board.by_color.white)
                 == Bitboard(0)
@@ -372,7 +418,8 @@ impl Game {
             index += 1;
         }
 
-        if self.black_castling_rights.queen_side
+        if !captures_only
+            && self.black_castling_rights.queen_side
             && queenside_castle_mask_without_king
                 & (self.board.by_color.black | self.board.by_color.white)
                 == Bitboard(0)
@@ -525,19 +572,19 @@ impl Game {
 
         // castling
         if uci == "e1g1" && matches!(role, Role::King) {
-            self.play(Move::kingside_castle(true));
+            self.make_move(Move::kingside_castle(true));
             return Ok(());
         }
         if uci == "e1c1" && matches!(role, Role::King) {
-            self.play(Move::queenside_castle(true));
+            self.make_move(Move::queenside_castle(true));
             return Ok(());
         }
         if uci == "e8g8"  && matches!(role, Role::King){
-            self.play(Move::kingside_castle(false));
+            self.make_move(Move::kingside_castle(false));
             return Ok(());
         }
         if uci == "e8c8"  && matches!(role, Role::King){
-            self.play(Move::queenside_castle(false));
+            self.make_move(Move::queenside_castle(false));
             return Ok(());
         }
         let capture = self.board.piece_at(to.0 as i32);
@@ -566,7 +613,7 @@ impl Game {
                 promotion,
                 MoveType::Promotion,
             );
-            self.play(played_move);
+            self.make_move(played_move);
             return Ok(());
         }
 
@@ -579,8 +626,8 @@ impl Game {
 
         // double pawn push
         if matches!(role, Role::Pawn)
-            && ((from.rank() == 1 && to.0.wrapping_sub(from.0) == 2)
-                || (from.rank() == 6 && from.0.wrapping_sub(to.0) == 2))
+            && ((from.rank() == 1 && to.rank() == 3)
+                || (from.rank() == 6 && to.rank() == 4))
         {
             let played_move = Move::new(
                 self.is_white,
@@ -597,7 +644,7 @@ impl Game {
                 PromotionRole::Queen,
                 MoveType::DoublePawnPush,
             );
-            self.play(played_move);
+            self.make_move(played_move);
             return Ok(());
         }
 
@@ -617,7 +664,7 @@ impl Game {
                 PromotionRole::Queen,
                 MoveType::EnPassant,
             );
-            self.play(move1);
+            self.make_move(move1);
             return Ok(());
         }
 
@@ -637,20 +684,38 @@ impl Game {
             MoveType::Quiet,
         );
 
-        self.play(move1);
+        self.make_move(move1);
         Ok(())
     }
 
-    pub fn play(&mut self, played_move: Move) {
+    pub fn make_move(&mut self, played_move: Move) {
+        #[cfg(feature = "nnue")]
+        let before = self.board;
         let from_square = Bitboard(1u64 << played_move.from().0 as u64);
         let to_square = Bitboard(1u64 << played_move.to().0 as u64);
 
-        self.en_passant_target = Option::None;
-        self.halfmove_clock += 1;
+        let captured = match played_move.move_type() {
+            MoveType::EnPassant => Some(Role::Pawn),
+            _ if played_move.is_capture() => Some(played_move.capture_role()),
+            _ => None,
+        };
+        self.undo.push(Undo {
+            played_move,
+            captured,
+            white_castling_rights: self.white_castling_rights,
+            black_castling_rights: self.black_castling_rights,
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            zobrist: self.board.zobrist,
+        });
+        self.board.zobrist ^= self.state_key();
 
-        // 50 move rule
-        if self.halfmove_clock >= 50 {
-            self.outcome = Outcome::Draw(DrawType::FitftyMoveRule);
+        self.en_passant_target = Option::None;
+        // captures and pawn moves can't be undone, the positions before them can't come again
+        if captured.is_some() || matches!(played_move.role(), Role::Pawn) {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
         }
 
         // update biboards
@@ -684,9 +749,6 @@ impl Game {
                             }
                         }
                     }
-                    Role::Pawn => {
-                        self.halfmove_clock = 0;
-                    }
                     _ => {}
                 }
             }
@@ -768,7 +830,7 @@ impl Game {
                             role: Role::King,
                         },
                         Bitboard(0b1 << 60),
-                        Bitboard(0b1) << 58,
+                        Bitboard(0b1 << 58),
                     );
                     self.black_castling_rights.queen_side = false;
                     self.black_castling_rights.king_side = false;
@@ -834,7 +896,6 @@ impl Game {
 
         if played_move.is_capture() {
             let capture = played_move.capture_role();
-            self.halfmove_clock = 0;
             if !matches!(played_move.move_type(), MoveType::EnPassant) {
                 self.board.update_bitboard(
                     Piece {
@@ -868,29 +929,219 @@ impl Game {
             }
         }
 
+        #[cfg(feature = "nnue")]
+        {
+            self.nnue_undo.push(self.nnue);
+            if let Some(network) = nnue::network() {
+                self.nnue.update(network, &before, &self.board);
+            }
+        }
+
         if !self.is_white {
             self.fullmoves += 1;
         }
 
         self.is_white = !self.is_white;
+        self.board.zobrist ^= ZOBRIST_SIDE ^ self.state_key();
+
+        self.history.push(self.board.zobrist);
+    }
 
-        self.history.0[self.history.1] = self.board.zobrist;
-        self.history.1 += 1;
+    // takes back the last move, made with make_move or make_null_move
+    pub fn unmake_move(&mut self) {
+        let undo = self.undo.pop().expect("no move to take back");
+        self.history.pop();
 
-        if self.history.1 >= 200 {
-            self.history.1 = 0;
+        self.is_white = !self.is_white;
+        let m = undo.played_move;
+        if m != Move::null() {
+            if !self.is_white {
+                self.fullmoves -= 1;
+            }
+            self.unmake_pieces(m, undo.captured);
+            #[cfg(feature = "nnue")]
+            {
+                self.nnue = self.nnue_undo.pop().expect("no accumulators to take back");
+            }
         }
 
-        // 3 fold repetition
-        let mut count = 0;
-        for i in 0..self.history.1 {
-            if self.history.0[i] == self.board.zobrist {
-                count += 1;
+        self.white_castling_rights = undo.white_castling_rights;
+        self.black_castling_rights = undo.black_castling_rights;
+        self.en_passant_target = undo.en_passant_target;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.board.zobrist = undo.zobrist;
+    }
+
+    // the steps of make_move in reverse, every piece goes back to where it came from
+    fn unmake_pieces(&mut self, m: Move, captured: Option<Role>) {
+        let from_square = Bitboard(1u64 << m.from().0 as u64);
+        let to_square = Bitboard(1u64 << m.to().0 as u64);
+        let piece = |role| Piece {
+            is_white: m.is_white(),
+            role,
+        };
+
+        if let Some(role) = captured.filter(|_| !matches!(m.move_type(), MoveType::EnPassant)) {
+            let captured_piece = Piece {
+                is_white: !m.is_white(),
+                role,
+            };
+            self.board.update_bitboard(captured_piece, to_square, to_square);
+        }
+
+        // castling moves are on the back rank of the side that castles
+        let rank = if m.is_white() { 0 } else { 56 };
+        match m.move_type() {
+            MoveType::Quiet | MoveType::DoublePawnPush => {
+                self.board.update_bitboard(m.piece(), to_square, from_square);
+            }
+            MoveType::KingsideCastle => {
+                self.board.update_bitboard(piece(Role::King), Bitboard(1 << (rank + 6)), Bitboard(1 << (rank + 4)));
+                self.board.update_bitboard(piece(Role::Rook), Bitboard(1 << (rank + 5)), Bitboard(1 << (rank + 7)));
+            }
+            MoveType::QueensideCastle => {
+                self.board.update_bitboard(piece(Role::King), Bitboard(1 << (rank + 2)), Bitboard(1 << (rank + 4)));
+                self.board.update_bitboard(piece(Role::Rook), Bitboard(1 << (rank + 3)), Bitboard(1 << rank));
+            }
+            MoveType::EnPassant => {
+                let captured_square = if m.is_white() { m.to().0 - 8 } else { m.to().0 + 8 };
+                let captured_bitboard = Bitboard(1 << captured_square);
+                let enemy_pawn = Piece {
+                    is_white: !m.is_white(),
+                    role: Role::Pawn,
+                };
+                self.board.update_bitboard(enemy_pawn, captured_bitboard, captured_bitboard);
+                self.board.update_bitboard(piece(Role::Pawn), to_square, from_square);
+            }
+            MoveType::Promotion => {
+                let promoted = match m.promotion_role() {
+                    PromotionRole::Queen => Role::Queen,
+                    PromotionRole::Rook => Role::Rook,
+                    PromotionRole::Bishop => Role::Bishop,
+                    PromotionRole::Knight => Role::Knight,
+                };
+                self.board.update_bitboard(piece(promoted), to_square, to_square);
+                self.board.update_bitboard(piece(Role::Pawn), from_square, from_square);
             }
         }
+    }
 
-        if count >= 3 {
-            self.outcome = Outcome::Draw(DrawType::ThreefoldRepitition);
+    // passes the turn without moving, only used by null-move pruning
+    pub fn make_null_move(&mut self) {
+        self.undo.push(Undo {
+            played_move: Move::null(),
+            captured: None,
+            white_castling_rights: self.white_castling_rights,
+            black_castling_rights: self.black_castling_rights,
+            en_passant_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            zobrist: self.board.zobrist,
+        });
+        self.board.zobrist ^= self.state_key();
+        self.en_passant_target = None;
+        // no position before passing can come again after it, a real game has no null moves
+        self.halfmove_clock = 0;
+        self.is_white = !self.is_white;
+        self.board.zobrist ^= ZOBRIST_SIDE ^ self.state_key();
+        self.history.push(self.board.zobrist);
+    }
+
+    // the part of the zobrist key the board can't know: castling rights, and the en passant
+    // file when a pawn could take there, without one the position is the same as before
+    fn state_key(&self) -> u64 {
+        let rights = [
+            self.white_castling_rights.king_side,
+            self.white_castling_rights.queen_side,
+            self.black_castling_rights.king_side,
+            self.black_castling_rights.queen_side,
+        ];
+        let mut key = rights
+            .into_iter()
+            .zip(ZOBRIST_CASTLING)
+            .filter(|&(right, _)| right)
+            .fold(0, |key, (_, value)| key ^ value);
+        if let Some(target) = self.en_passant_target {
+            let attackers = Board::pawn_attacks(!self.is_white, Bitboard(1 << target.0))
+                & self.board.my_bitboard(self.is_white)
+                & self.board.by_role.pawns;
+            if attackers != Bitboard(0) {
+                key ^= ZOBRIST_EN_PASSANT[target.0 as usize % 8];
+            }
+        }
+        key
+    }
+
+    // distances in plies to the earlier occurrences of the current position, nearest first.
+    // Only the positions since the last capture or pawn move can repeat, and only every
+    // other one has the same side to move.
+    fn repeated_at(&self) -> impl Iterator<Item = usize> + '_ {
+        let last = self.history.len() - 1;
+        let key = self.history[last];
+        let reach = (self.halfmove_clock.max(0) as usize).min(last);
+        (4..=reach)
+            .step_by(2)
+            .filter(move |&distance| self.history[last - distance] == key)
+    }
+
+    // the fifty-move rule, unless the last move before it gave mate
+    fn fifty_moves_passed(&self) -> bool {
+        self.halfmove_clock >= 100 && (!self.in_check() || self.get_legal_moves().1 > 0)
+    }
+
+    // no sequence of moves can mate: bare kings, a single knight, or bishops that all
+    // stand on squares of one colour (a bishop each included)
+    pub fn insufficient_material(&self) -> bool {
+        let roles = &self.board.by_role;
+        if roles.pawns | roles.rooks | roles.queens != Bitboard(0) {
+            return false;
+        }
+        match roles.knights.0.count_ones() {
+            0 => {
+                roles.bishops & Bitboard::LIGHT_SQUARES == Bitboard(0)
+                    || roles.bishops & !Bitboard::LIGHT_SQUARES == Bitboard(0)
+            }
+            1 => roles.bishops == Bitboard(0),
+            _ => false,
+        }
+    }
+
+    // whether the search `ply` moves after its root should score the position as a draw.
+    // A position that repeats one from inside the search can be repeated once more, so
+    // that already counts, while those before the root need the third occurrence.
+    pub fn is_draw(&self, ply: usize) -> bool {
+        if self.fifty_moves_passed() || self.insufficient_material() {
+            return true;
+        }
+        let mut before_root = 0;
+        for distance in self.repeated_at() {
+            if distance < ply {
+                return true;
+            }
+            before_root += 1;
+            if before_root >= 2 {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[allow(dead_code)]
+    pub fn outcome(&self) -> Outcome {
+        if self.get_legal_moves().1 == 0 {
+            return if self.in_check() {
+                Outcome::Win(!self.is_white)
+            } else {
+                Outcome::Draw(DrawType::Stalemate)
+            };
+        }
+        if self.halfmove_clock >= 100 {
+            Outcome::Draw(DrawType::FitftyMoveRule)
+        } else if self.repeated_at().count() >= 2 {
+            Outcome::Draw(DrawType::ThreefoldRepitition)
+        } else if self.insufficient_material() {
+            Outcome::Draw(DrawType::InsufficientMaterial)
+        } else {
+            Outcome::Playing
         }
     }
 
@@ -959,16 +1210,249 @@ impl Game {
             }
         };
 
-        Ok(Game {
-            board: Board::from_fen(fen),
+        let mut board = Board::from_fen(fen);
+        if !is_white {
+            board.zobrist ^= ZOBRIST_SIDE;
+        }
+        let mut game = Game {
+            board,
             is_white,
             white_castling_rights: castlig_rights.0,
             black_castling_rights: castlig_rights.1,
             en_passant_target: en_passent_target,
             halfmove_clock,
             fullmoves,
-            outcome: Outcome::Playing,
-            history: ([0; 200], 0),
-        })
+            history: vec![board.zobrist],
+            undo: Vec::new(),
+            #[cfg(feature = "nnue")]
+            nnue: nnue::Accumulators::new(&board),
+            #[cfg(feature = "nnue")]
+            nnue_undo: Vec::new(),
+        };
+        game.board.zobrist ^= game.state_key();
+        game.history[0] = game.board.zobrist;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_are_the_noisy_legal_moves() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let game = Game::from_fen(fen).expect("invalid FEN");
+            let (moves, count) = game.get_legal_moves();
+            let (captures, capture_count) = game.get_legal_captures();
+
+            let mut expected: Vec<u32> = moves[0..count]
+                .iter()
+                .filter(|m| {
+                    m.is_capture()
+                        || matches!(m.move_type(), MoveType::EnPassant | MoveType::Promotion)
+                })
+                .map(|m| m.0)
+                .collect();
+            let mut found: Vec<u32> = captures[0..capture_count].iter().map(|m| m.0).collect();
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn in_check() {
+        let game = Game::from_fen("rnbqkbnr/ppppp1pp/8/5p1Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2")
+            .expect("invalid FEN");
+        assert!(game.in_check());
+        let game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .expect("invalid FEN");
+        assert!(!game.in_check());
+    }
+
+    #[test]
+    fn black_castles_queenside() {
+        let mut game = Game::from_fen("r3k3/8/8/8/8/8/8/4K3 b q - 0 1").expect("invalid FEN");
+        game.play_uci("e8c8").expect("illegal move");
+        let king = game.board.by_role.kings & game.board.by_color.black;
+        let rook = game.board.by_role.rooks & game.board.by_color.black;
+        assert_eq!(king.0, 1 << 58);
+        assert_eq!(rook.0, 1 << 59);
+    }
+
+    #[test]
+    fn null_move_passes_the_turn() {
+        let mut game = Game::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+            .expect("invalid FEN");
+        let key = game.board.zobrist;
+        game.make_null_move();
+        assert!(!game.is_white);
+        assert!(game.en_passant_target.is_none());
+        assert_ne!(game.board.zobrist, key);
+        game.unmake_move();
+        assert!(game.is_white);
+        assert_eq!(game.board.zobrist, key);
+        assert!(game.en_passant_target.is_some());
+    }
+
+    #[test]
+    fn dead_positions() {
+        for (fen, dead) in [
+            ("8/8/4k3/8/8/3K4/8/8 w - - 0 1", true),
+            ("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1", true),
+            ("8/8/4k3/8/8/3K4/8/6N1 b - - 0 1", true),
+            // both bishops on dark squares, then on opposite colours
+            ("2b5/8/4k3/8/8/3K4/8/5B2 w - - 0 1", true),
+            ("1b6/8/4k3/8/8/3K4/8/5B2 w - - 0 1", false),
+            ("8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1", false),
+            ("8/8/4k3/8/8/3K4/8/5BN1 w - - 0 1", false),
+            ("8/8/4k3/8/8/3K4/7P/8 w - - 0 1", false),
+        ] {
+            let game = Game::from_fen(fen).expect("invalid FEN");
+            assert_eq!(game.insufficient_material(), dead, "{}", fen);
+            assert_eq!(game.is_draw(0), dead, "{}", fen);
+        }
+        let game = Game::from_fen("8/8/4k3/8/8/3K4/8/5B2 w - - 0 1").expect("invalid FEN");
+        assert!(matches!(game.outcome(), Outcome::Draw(DrawType::InsufficientMaterial)));
+    }
+
+    fn play_all(game: &mut Game, moves: &str) {
+        for uci in moves.split_whitespace() {
+            game.play_uci(uci).expect("illegal move");
+        }
+    }
+
+    #[test]
+    fn repetitions_back_to_the_last_pawn_move() {
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .expect("invalid FEN");
+        play_all(&mut game, "g1f3 g8f6 f3g1 f6g8");
+        // once in the game's moves, a draw only when the search itself went around
+        assert!(!game.is_draw(0));
+        assert!(game.is_draw(5));
+        assert!(matches!(game.outcome(), Outcome::Playing));
+
+        play_all(&mut game, "g1f3 g8f6 f3g1 f6g8");
+        assert!(game.is_draw(0));
+        assert!(matches!(game.outcome(), Outcome::Draw(DrawType::ThreefoldRepitition)));
+
+        // the pawn move makes every earlier position unreachable
+        play_all(&mut game, "e2e4 e7e5 g1f3 g8f6 f3g1 f6g8");
+        assert!(!game.is_draw(0));
+        assert!(game.is_draw(7));
+    }
+
+    #[test]
+    fn lost_castling_rights_make_a_new_position() {
+        let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .expect("invalid FEN");
+        // the rook's trip costs white the kingside castling, the position with it occurred once
+        play_all(&mut game, "g1f3 g8f6 h1g1 f6g8 g1h1 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8");
+        assert!(!game.is_draw(0));
+        assert!(matches!(game.outcome(), Outcome::Playing));
+        assert_ne!(game.history[0], game.board.zobrist);
+    }
+
+    #[test]
+    fn en_passant_counts_only_when_it_can_be_taken() {
+        let key = |fen| Game::from_fen(fen).expect("invalid FEN").board.zobrist;
+        let mut game = Game::from_fen("4k3/8/8/8/5p2/8/4P3/4K3 w - - 0 1").expect("invalid FEN");
+        game.play_uci("e2e4").expect("illegal move");
+        assert_eq!(game.board.zobrist, key("4k3/8/8/8/4Pp2/8/8/4K3 b - e3 0 1"));
+        assert_ne!(game.board.zobrist, key("4k3/8/8/8/4Pp2/8/8/4K3 b - - 0 1"));
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").expect("invalid FEN");
+        game.play_uci("e2e4").expect("illegal move");
+        assert_eq!(game.board.zobrist, key("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1"));
+    }
+
+    #[test]
+    fn clock_resets_on_pawn_moves_and_captures() {
+        let mut game = Game::from_fen("4k3/8/8/3p4/8/8/4P3/4K1N1 w - - 10 30").expect("invalid FEN");
+        game.play_uci("g1f3").expect("illegal move");
+        assert_eq!(game.halfmove_clock, 11);
+        game.play_uci("d5d4").expect("illegal move");
+        assert_eq!(game.halfmove_clock, 0);
+        play_all(&mut game, "f3e5 e8d8 e2e4");
+        assert_eq!(game.halfmove_clock, 0);
+        game.play_uci("d4e3").expect("illegal move");
+        assert_eq!(game.halfmove_clock, 0);
+        game.unmake_move();
+        assert_eq!(game.halfmove_clock, 0);
+        assert!(game.en_passant_target.is_some());
+    }
+
+    #[test]
+    fn fifty_moves_are_a_hundred_plies() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/R7/4K3 w - - 98 80").expect("invalid FEN");
+        game.play_uci("a2b2").expect("illegal move");
+        assert!(!game.is_draw(0));
+        game.play_uci("e8f8").expect("illegal move");
+        assert!(game.is_draw(0));
+        assert!(matches!(game.outcome(), Outcome::Draw(DrawType::FitftyMoveRule)));
+
+        // mate on the hundredth ply still wins
+        let mut game = Game::from_fen("6k1/R7/6K1/8/8/8/8/8 w - - 99 80").expect("invalid FEN");
+        game.play_uci("a7a8").expect("illegal move");
+        assert_eq!(game.halfmove_clock, 100);
+        assert!(!game.is_draw(0));
+        assert!(matches!(game.outcome(), Outcome::Win(true)));
+    }
+
+    // every field of the position, to compare before and after a move is taken back
+    fn snapshot(game: &Game) -> String {
+        let board = &game.board;
+        format!(
+            "{:?} {:?} {} {} {} {} {} {} {:?} {:?} {:?} {} {} {:?}",
+            board.by_color,
+            board.by_role,
+            board.zobrist,
+            board.pawn_zobrist,
+            board.mg_score,
+            board.eg_score,
+            board.phase,
+            game.is_white,
+            game.white_castling_rights,
+            game.black_castling_rights,
+            game.en_passant_target.map(|square| square.0),
+            game.halfmove_clock,
+            game.fullmoves,
+            game.history,
+        )
+    }
+
+    #[test]
+    fn unmake_restores_the_position() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "r3k2r/8/8/8/3pPp2/8/8/R3K2R b KQkq e3 0 1",
+        ] {
+            let mut game = Game::from_fen(fen).expect("invalid FEN");
+            let before = snapshot(&game);
+            let (moves, count) = game.get_legal_moves();
+            for &m in &moves[0..count] {
+                game.make_move(m);
+                let (replies, reply_count) = game.get_legal_moves();
+                for &reply in &replies[0..reply_count] {
+                    let after = snapshot(&game);
+                    game.make_move(reply);
+                    game.unmake_move();
+                    assert_eq!(snapshot(&game), after, "{} {} {}", fen, m, reply);
+                }
+                game.unmake_move();
+                assert_eq!(snapshot(&game), before, "{} {}", fen, m);
+            }
+        }
     }
 }
@@ -1,11 +1,16 @@
 pub mod bitboard;
 mod bmi;
 pub mod board;
+pub mod eval;
 pub mod game;
 mod lookup;
 pub mod r#move;
+#[cfg(feature = "nnue")]
+pub mod nnue;
+mod pawns;
 mod piece;
 mod role;
+pub mod tune;
 mod uci;
 mod values;
 mod score;
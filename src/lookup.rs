@@ -585,5 +585,22 @@ pub fn generate_zobrist_numbers() -> std::io::Result<()> {
     for mv in moves {
         writeln!(output, "\t0x{:x},", mv);
     }
+    writeln!(output, "];\n")?;
+    writeln!(output, "// toggled with the side to move")?;
+    writeln!(output, "pub const ZOBRIST_SIDE: u64 = 0x{:x};\n", rand::random::<u64>())?;
+    writeln!(
+        output,
+        "// white kingside, white queenside, black kingside and black queenside castling"
+    )?;
+    writeln!(output, "pub const ZOBRIST_CASTLING: [u64; 4] = [")?;
+    for _ in 0..4 {
+        writeln!(output, "\t0x{:x},", rand::random::<u64>())?;
+    }
+    writeln!(output, "];\n")?;
+    writeln!(output, "// by the file of the en passant square")?;
+    writeln!(output, "pub const ZOBRIST_EN_PASSANT: [u64; 8] = [")?;
+    for _ in 0..8 {
+        writeln!(output, "\t0x{:x},", rand::random::<u64>())?;
+    }
     writeln!(output, "];")
 }
@@ -771,3 +771,26 @@ pub const ZOBRIST_VALUES: [u64; 768] = [
 	0xcda96842571d6f48,
 	0x8c8fb737386a2a60,
 ];
+
+// toggled with the side to move
+pub const ZOBRIST_SIDE: u64 = 0x5d1c2f3a9be47c61;
+
+// white kingside, white queenside, black kingside and black queenside castling
+pub const ZOBRIST_CASTLING: [u64; 4] = [
+	0x7c3e62447ce57e9,
+	0x2ec746997017125e,
+	0x1f1d1f01a9d9a510,
+	0xe46893867c089f4e,
+];
+
+// by the file of the en passant square
+pub const ZOBRIST_EN_PASSANT: [u64; 8] = [
+	0x86056a0acb0b79a2,
+	0x87cfffacf078f425,
+	0xc0df8eb985855a47,
+	0xf13a2d6e8e1ae976,
+	0xdb0af0c78dab8a6c,
+	0x964dc0c2546e2301,
+	0x7a451e772d22bf79,
+	0xfa8c2e87ecdc92f9,
+];
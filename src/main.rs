@@ -1,25 +1,52 @@
 use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use clap::Parser;
 use lookup::generate_zobrist_numbers;
 use uci::RecceiveUCI;
 
+use crate::options::{Options, Spin, CLEAR_HASH};
+#[cfg(feature = "nnue")]
+use crate::options::Text;
+use crate::pawns::PawnTable;
+use crate::search::Signals;
+use crate::tt::TranspositionTable;
 use crate::{game::Game, uci::SendUCI};
 use crate::uci::Score::{CP, Mate};
 
 mod bitboard;
 mod bmi;
 mod board;
+mod eval;
 mod game;
 mod lookup;
 pub mod r#move;
+mod movepick;
+#[cfg(feature = "nnue")]
+mod nnue;
+mod options;
+mod pawns;
 mod piece;
 mod role;
 mod search;
+mod time;
+mod tt;
 mod uci;
 mod values;
 mod score;
+
+// signals a running search to stop and waits until it has sent its bestmove
+fn stop_search(search_thread: &mut Option<JoinHandle<()>>, signals: &Signals) {
+    if let Some(handle) = search_thread.take() {
+        signals.stop.store(true, Ordering::Relaxed);
+        handle.join().expect("search thread panicked");
+    }
+}
+
 fn main() {
     let stdin = stdin();
     let mut stdout = stdout();
@@ -27,26 +54,71 @@ fn main() {
 
     let mut game = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
         .expect("invalid fen");
+    let mut options = Options::new();
+    let mut tt = Arc::new(TranspositionTable::new(options.spin(Spin::Hash) as usize));
+    let signals = Arc::new(Signals::default());
+    let mut search_thread: Option<JoinHandle<()>> = None;
 
     loop {
         buffer.clear();
 
-        stdin.lock().read_line(&mut buffer).unwrap();
+        let message = match stdin.lock().read_line(&mut buffer) {
+            Ok(0) | Err(_) => RecceiveUCI::Quit,
+            Ok(_) => RecceiveUCI::parse_str(&buffer),
+        };
 
-        let message = RecceiveUCI::parse_str(&buffer);
+        // the search only keeps running while we answer commands that don't change its state
+        if !matches!(
+            message,
+            RecceiveUCI::IsReady
+                | RecceiveUCI::Debug(_)
+                | RecceiveUCI::PonderHit
+                | RecceiveUCI::Unknown(_)
+        ) {
+            stop_search(&mut search_thread, &signals);
+        }
 
         match message {
             RecceiveUCI::UCI => {
                 writeln!(stdout, "id name gégène").unwrap();
                 writeln!(stdout, "id author Silas Pachali").unwrap();
+                for option in options.iter() {
+                    writeln!(stdout, "{}", option).unwrap();
+                }
                 writeln!(stdout, "uciok").unwrap();
             }
             RecceiveUCI::Debug(_) => {}
             RecceiveUCI::IsReady => {
                 writeln!(stdout, "{}", SendUCI::ReadyOk.to_str()).unwrap();
             }
-            RecceiveUCI::SetOption { id: _, value: _ } => {}
-            RecceiveUCI::UCINewGame => {}
+            RecceiveUCI::SetOption { id, value } => match options.set(&id, value.as_deref()) {
+                Ok(name) if name == Spin::Hash.name() => {
+                    tt = Arc::new(TranspositionTable::new(options.spin(Spin::Hash) as usize))
+                }
+                Ok(CLEAR_HASH) => tt.clear(),
+                #[cfg(feature = "nnue")]
+                Ok(name) if name == Text::EvalFile.name() => {
+                    // the search was stopped above, nothing uses the old network anymore
+                    let message = match unsafe { nnue::load(options.string(Text::EvalFile)) } {
+                        Ok(()) if options.string(Text::EvalFile).is_empty() => {
+                            "EvalFile: using the classical evaluation".to_string()
+                        }
+                        Ok(()) => format!("EvalFile: loaded {}", options.string(Text::EvalFile)),
+                        Err(e) => format!("EvalFile: {}", e),
+                    };
+                    writeln!(stdout, "{}", SendUCI::Info(uci::Info::String(message)).to_str()).unwrap();
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    writeln!(
+                        stdout,
+                        "{}",
+                        SendUCI::Info(uci::Info::String(format!("{}: {}", id, e))).to_str()
+                    )
+                        .unwrap();
+                }
+            },
+            RecceiveUCI::UCINewGame => tt.clear(),
             RecceiveUCI::Position { position, moves } => {
                 match position {
                     uci::Position::Startpos => {
@@ -63,39 +135,47 @@ fn main() {
                     game.play_uci(move1).expect("error playing moves");
                 }
             }
-            RecceiveUCI::Go {
-                ponder,
-                time_control,
-                depth,
-            } => {
-                let result = search::search(game, time_control);
+            RecceiveUCI::Go { ponder, limits } => {
+                signals.stop.store(false, Ordering::Relaxed);
+                signals.ponder.store(ponder, Ordering::Relaxed);
+                let tt = Arc::clone(&tt);
+                let signals = Arc::clone(&signals);
+                let options = options.clone();
+                let game = game.clone();
 
-                writeln!(
-                    stdout,
-                    "{}",
-                    SendUCI::BestMove {
-                        move1: result.best_move,
-                        ponder: None,
+                search_thread = Some(thread::spawn(move || {
+                    let result = search::search(game, &limits, &tt, &options, &signals);
+
+                    // bestmove may only be sent after ponderhit or stop, or stop in infinite mode
+                    while (limits.infinite || signals.ponder.load(Ordering::Relaxed))
+                        && !signals.stop.load(Ordering::Relaxed)
+                    {
+                        thread::sleep(Duration::from_millis(1));
                     }
-                        .to_str()
-                )
-                    .unwrap();
-                writeln!(
-                    stdout,
-                    "{}",
-                    SendUCI::Info(uci::Info::Time(result.time)).to_str()
-                )
-                    .unwrap();
-                writeln!(
-                    stdout,
-                    "info score {}",
-                    result.best_score
-                )
-                    .unwrap();
+
+                    let mut stdout = std::io::stdout().lock();
+                    writeln!(
+                        stdout,
+                        "{}",
+                        SendUCI::BestMove {
+                            move1: result.best_move,
+                            ponder: result.ponder_move,
+                        }
+                            .to_str()
+                    )
+                        .unwrap();
+                    stdout.flush().expect("Failed to flush stdout");
+                }));
             }
             RecceiveUCI::Stop => {}
-            RecceiveUCI::PonderHit => {}
+            RecceiveUCI::PonderHit => signals.ponder.store(false, Ordering::Relaxed),
             RecceiveUCI::Quit => return,
+            RecceiveUCI::Eval => {
+                writeln!(stdout, "{}", eval::trace(&game.board)).unwrap();
+                let score = search::evaluate(&game, &mut PawnTable::new());
+                let score = if game.is_white { score } else { -score };
+                writeln!(stdout, "Final evaluation: {} cp (white side)", score).unwrap();
+            }
             RecceiveUCI::Unknown(_) => {
                 let _ = stdout.write_all("Unknown command\n".as_bytes());
             }
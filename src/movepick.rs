@@ -0,0 +1,346 @@
+use crate::board::Board;
+use crate::r#move::{Move, MoveType};
+use crate::role::{PromotionRole, Role};
+
+// history scores stay within this bound, the gravity formula slows them down near it
+pub const HISTORY_MAX: i32 = 16_384;
+
+// piece and target square of the moving piece, white pieces first
+fn piece_square(m: Move) -> usize {
+    let piece = m.role() as usize + if m.is_white() { 0 } else { 6 };
+    piece * 64 + m.to().0 as usize
+}
+
+fn from_to(m: Move) -> usize {
+    (m.is_white() as usize * 64 + m.from().0 as usize) * 64 + m.to().0 as usize
+}
+
+// large bonuses move an entry less the closer it already is to the bound
+fn gravity(entry: &mut i16, bonus: i32) {
+    let value = *entry as i32;
+    *entry = (value + bonus - value * bonus.abs() / HISTORY_MAX) as i16;
+}
+
+fn bonus(depth: u32) -> i32 {
+    let depth = depth as i32;
+    (16 * depth * depth + 32 * depth).min(1200)
+}
+
+pub fn is_quiet(m: Move) -> bool {
+    !m.is_capture() && !matches!(m.move_type(), MoveType::Promotion | MoveType::EnPassant)
+}
+
+// quiet move statistics, all fed by beta cutoffs
+pub struct History {
+    // butterfly table by side, from and to square
+    butterfly: Vec<i16>,
+    // the move that refuted a move, by the refuted move's piece and target
+    countermoves: Vec<Move>,
+    // by piece and target of a move one or two plies earlier in the line, then of this one
+    continuation: Vec<i16>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            butterfly: vec![0; 2 * 64 * 64],
+            countermoves: vec![Move::null(); 12 * 64],
+            continuation: vec![0; 12 * 64 * 12 * 64],
+        }
+    }
+
+    // the moves played one and two plies ago, the null move is skipped
+    pub fn quiet_score(&self, m: Move, previous: &[Move; 2]) -> i32 {
+        let mut score = self.butterfly[from_to(m)] as i32;
+        for &earlier in previous.iter().filter(|&&earlier| earlier != Move::null()) {
+            score += self.continuation[piece_square(earlier) * 768 + piece_square(m)] as i32;
+        }
+        score
+    }
+
+    pub fn countermove(&self, previous: Move) -> Move {
+        if previous == Move::null() {
+            return Move::null();
+        }
+        self.countermoves[piece_square(previous)]
+    }
+
+    // rewards the quiet move that caused a cutoff and punishes the quiets tried before it
+    pub fn update(&mut self, best: Move, tried: &[Move], previous: &[Move; 2], depth: u32) {
+        let bonus = bonus(depth);
+        self.add(best, previous, bonus);
+        for &m in tried {
+            self.add(m, previous, -bonus);
+        }
+        if previous[0] != Move::null() {
+            self.countermoves[piece_square(previous[0])] = best;
+        }
+    }
+
+    fn add(&mut self, m: Move, previous: &[Move; 2], bonus: i32) {
+        gravity(&mut self.butterfly[from_to(m)], bonus);
+        for &earlier in previous.iter().filter(|&&earlier| earlier != Move::null()) {
+            gravity(
+                &mut self.continuation[piece_square(earlier) * 768 + piece_square(m)],
+                bonus,
+            );
+        }
+    }
+}
+
+pub fn captured_value(m: Move) -> i32 {
+    match m.move_type() {
+        MoveType::EnPassant => Role::Pawn.value(),
+        _ if m.is_capture() => m.capture_role().value(),
+        _ => 0,
+    }
+}
+
+// most valuable victim first, least valuable attacker among equal victims
+pub fn mvv_lva(m: Move) -> i32 {
+    let promotion = match m.move_type() {
+        MoveType::Promotion => match m.promotion_role() {
+            PromotionRole::Queen => Role::Queen.value(),
+            PromotionRole::Rook => Role::Rook.value(),
+            PromotionRole::Bishop => Role::Bishop.value(),
+            PromotionRole::Knight => Role::Knight.value(),
+        },
+        _ => 0,
+    };
+    if captured_value(m) == 0 && promotion == 0 {
+        return 0;
+    }
+    10 * (captured_value(m) + promotion) - m.role().value() / 100
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Stage {
+    TableMove,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+// hands out the legal moves best first, sorting each stage only as far as it is used.
+// `moves[..noisy_end]` holds captures and promotions: those losing material are
+// swapped to the front and tried last, the ones already tried follow them.
+pub struct MovePicker {
+    moves: [Move; 218],
+    scores: [i32; 218],
+    count: usize,
+    noisy_end: usize,
+    bad_end: usize,
+    index: usize,
+    stage: Stage,
+    tt_move: Move,
+    killers: [Move; 2],
+    counter: Move,
+    previous: [Move; 2],
+}
+
+impl MovePicker {
+    pub fn new(
+        moves: [Move; 218],
+        count: usize,
+        tt_move: Option<Move>,
+        killers: [Move; 2],
+        counter: Move,
+        previous: [Move; 2],
+    ) -> MovePicker {
+        let mut picker = MovePicker {
+            moves,
+            scores: [0; 218],
+            count,
+            noisy_end: 0,
+            bad_end: 0,
+            index: 0,
+            stage: Stage::TableMove,
+            tt_move: Move::null(),
+            killers: [Move::null(); 2],
+            counter: Move::null(),
+            previous,
+        };
+
+        for i in 0..count {
+            if !is_quiet(picker.moves[i]) {
+                picker.moves.swap(i, picker.noisy_end);
+                picker.scores[picker.noisy_end] = mvv_lva(picker.moves[picker.noisy_end]);
+                picker.noisy_end += 1;
+            }
+        }
+
+        // the table move may come from another position with the same key
+        if let Some(tt_move) = tt_move.filter(|m| picker.contains(0, count, *m)) {
+            picker.tt_move = tt_move;
+        }
+        for (i, killer) in killers.into_iter().enumerate() {
+            if !picker.is_special(killer) && picker.contains(picker.noisy_end, count, killer) {
+                picker.killers[i] = killer;
+            }
+        }
+        if !picker.is_special(counter) && picker.contains(picker.noisy_end, count, counter) {
+            picker.counter = counter;
+        }
+        picker
+    }
+
+    fn contains(&self, start: usize, end: usize, m: Move) -> bool {
+        m != Move::null() && self.moves[start..end].contains(&m)
+    }
+
+    // moves handed out by a stage of their own
+    fn is_special(&self, m: Move) -> bool {
+        m == self.tt_move || m == self.killers[0] || m == self.killers[1] || m == self.counter
+    }
+
+    // swaps the best scored move left before `end` to `index`
+    fn select(&mut self, end: usize) -> Move {
+        let mut best = self.index;
+        for i in self.index + 1..end {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.index, best);
+        self.scores.swap(self.index, best);
+        self.moves[self.index]
+    }
+
+    pub fn next(&mut self, board: &Board, history: &History) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TableMove => {
+                    self.stage = Stage::GoodCaptures;
+                    if self.tt_move != Move::null() {
+                        return Some(self.tt_move);
+                    }
+                }
+                Stage::GoodCaptures => {
+                    if self.index >= self.noisy_end {
+                        self.stage = Stage::Killers;
+                        self.index = 0;
+                        continue;
+                    }
+                    let m = self.select(self.noisy_end);
+                    if m == self.tt_move {
+                        self.index += 1;
+                        continue;
+                    }
+                    if !board.see_ge(m, 0) {
+                        self.moves.swap(self.index, self.bad_end);
+                        self.scores.swap(self.index, self.bad_end);
+                        self.bad_end += 1;
+                        self.index += 1;
+                        continue;
+                    }
+                    self.index += 1;
+                    return Some(m);
+                }
+                Stage::Killers => {
+                    if self.index >= 2 {
+                        self.stage = Stage::Countermove;
+                        continue;
+                    }
+                    let killer = self.killers[self.index];
+                    self.index += 1;
+                    if killer != Move::null() {
+                        return Some(killer);
+                    }
+                }
+                Stage::Countermove => {
+                    self.stage = Stage::Quiets;
+                    for i in self.noisy_end..self.count {
+                        self.scores[i] = history.quiet_score(self.moves[i], &self.previous);
+                    }
+                    self.index = self.noisy_end;
+                    if self.counter != Move::null() {
+                        return Some(self.counter);
+                    }
+                }
+                Stage::Quiets => {
+                    if self.index >= self.count {
+                        self.stage = Stage::BadCaptures;
+                        self.index = 0;
+                        continue;
+                    }
+                    let m = self.select(self.count);
+                    self.index += 1;
+                    if !self.is_special(m) {
+                        return Some(m);
+                    }
+                }
+                Stage::BadCaptures => {
+                    if self.index >= self.bad_end {
+                        self.stage = Stage::Done;
+                        continue;
+                    }
+                    self.index += 1;
+                    return Some(self.moves[self.index - 1]);
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    fn picked(game: &Game, picker: &mut MovePicker, history: &History) -> Vec<String> {
+        let mut moves = Vec::new();
+        while let Some(m) = picker.next(&game.board, history) {
+            moves.push(m.to_algebraic());
+        }
+        moves
+    }
+
+    #[test]
+    fn every_move_once() {
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+            .expect("invalid fen");
+        let (moves, count) = game.get_legal_moves();
+        let killer = moves[0..count].iter().copied().find(|&m| is_quiet(m)).unwrap();
+        let mut picker = MovePicker::new(moves, count, Some(moves[5]), [killer, Move::null()], moves[7], [Move::null(); 2]);
+
+        let mut found = picked(&game, &mut picker, &History::new());
+        let mut expected: Vec<String> = moves[0..count].iter().map(|m| m.to_algebraic()).collect();
+        assert_eq!(found[0], moves[5].to_algebraic());
+        found.sort();
+        expected.sort();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn stages_in_order() {
+        // Qxd5 wins a pawn, Rxe5 loses the rook to the pawn on f6
+        let game = Game::from_fen("4k3/8/5p2/3pp3/8/8/8/3QR1K1 w - - 0 1").expect("invalid fen");
+        let (moves, count) = game.get_legal_moves();
+        let find = |uci: &str| *moves[0..count].iter().find(|m| m.to_algebraic() == uci).unwrap();
+
+        let mut history = History::new();
+        history.update(find("g1h2"), &[find("g1f2")], &[Move::null(); 2], 8);
+        let mut picker = MovePicker::new(moves, count, None, [find("d1a4"), Move::null()], Move::null(), [Move::null(); 2]);
+        let order = picked(&game, &mut picker, &history);
+
+        assert_eq!(order[0], "d1d5");
+        assert_eq!(order[1], "d1a4");
+        assert_eq!(order[2], "g1h2");
+        assert_eq!(order.last().unwrap(), "e1e5");
+        assert_eq!(order.len(), count);
+    }
+
+    #[test]
+    fn gravity_keeps_the_bound() {
+        let mut entry = 0;
+        for _ in 0..1000 {
+            gravity(&mut entry, 1200);
+        }
+        assert!(entry as i32 <= HISTORY_MAX);
+        assert!(entry as i32 > HISTORY_MAX - 100);
+    }
+}
@@ -0,0 +1,316 @@
+// HalfKA network: every piece on the board, seen relative to the king of each side,
+// feeds a hidden layer per side. Both halves go through a clipped relu into one output.
+//
+// The file is a flat list of little-endian i16, padded to 64 bytes at most:
+// feature weights [INPUTS][HIDDEN], feature biases [HIDDEN],
+// output weights [2 * HIDDEN] (side to move first) and the output bias.
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::bitboard::Bitboard;
+use crate::bmi::Bmi;
+use crate::board::Board;
+use crate::role::Role;
+
+pub const HIDDEN: usize = 256;
+// king square, colour relative to the perspective, role and square
+const INPUTS: usize = 64 * 2 * 6 * 64;
+
+// quantisation of the hidden and output layer, and centipawns per unit of output
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+const ROLES: [Role; 6] = [
+    Role::Pawn,
+    Role::Bishop,
+    Role::Knight,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+pub struct Network {
+    // tells the accumulators of different networks apart, unlike an address it isn't reused
+    id: usize,
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+static NETWORK: AtomicPtr<Network> = AtomicPtr::new(ptr::null_mut());
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn network() -> Option<&'static Network> {
+    unsafe { NETWORK.load(Ordering::Acquire).as_ref() }
+}
+
+/// An empty path unloads the network and goes back to the hand-written evaluation.
+///
+/// # Safety
+///
+/// The network loaded before is freed, no search may be running and nothing may
+/// hold on to what `network` returned.
+pub unsafe fn load(path: &str) -> Result<(), String> {
+    let network = if path.is_empty() {
+        ptr::null_mut()
+    } else {
+        let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+        Box::into_raw(Box::new(Network::from_bytes(&bytes)?))
+    };
+    let old = NETWORK.swap(network, Ordering::AcqRel);
+    if !old.is_null() {
+        drop(unsafe { Box::from_raw(old) });
+    }
+    Ok(())
+}
+
+fn pieces(board: &Board, is_white: bool, role: Role) -> Bitboard {
+    board.my_bitboard(is_white)
+        & match role {
+            Role::Pawn => board.by_role.pawns,
+            Role::Bishop => board.by_role.bishops,
+            Role::Knight => board.by_role.knights,
+            Role::Rook => board.by_role.rooks,
+            Role::Queen => board.by_role.queens,
+            Role::King => board.by_role.kings,
+        }
+}
+
+fn king_square(board: &Board, is_white: bool) -> usize {
+    pieces(board, is_white, Role::King).0.trailing_zeros() as usize % 64
+}
+
+// black sees the board flipped, so both sides use the same weights
+fn feature(perspective: usize, king: usize, is_white: bool, role: Role, square: usize) -> usize {
+    let flip = if perspective == 0 { 0 } else { 56 };
+    let colour = (is_white != (perspective == 0)) as usize;
+    (king ^ flip) * 768 + (colour * 6 + role as usize) * 64 + (square ^ flip)
+}
+
+impl Network {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        let expected = (INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1) * 2;
+        if bytes.len() < expected || bytes.len() - expected >= 64 {
+            return Err(format!("expected a network of {} bytes, got {}", expected, bytes.len()));
+        }
+
+        let mut values = bytes
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut take = |count: usize| values.by_ref().take(count).collect::<Vec<i16>>();
+
+        let feature_weights = take(INPUTS * HIDDEN);
+        let feature_bias = take(HIDDEN);
+        let output_weights = take(2 * HIDDEN);
+        let output_bias = take(1)[0];
+        Ok(Network {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    fn add(&self, values: &mut [i16; HIDDEN], feature: usize) {
+        let weights = &self.feature_weights[feature * HIDDEN..(feature + 1) * HIDDEN];
+        for (value, weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_add(*weight);
+        }
+    }
+
+    fn sub(&self, values: &mut [i16; HIDDEN], feature: usize) {
+        let weights = &self.feature_weights[feature * HIDDEN..(feature + 1) * HIDDEN];
+        for (value, weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_sub(*weight);
+        }
+    }
+
+    fn refresh(&self, accumulators: &mut Accumulators, board: &Board, perspective: usize) {
+        let values = &mut accumulators.values[perspective];
+        values.copy_from_slice(&self.feature_bias);
+
+        let king = king_square(board, perspective == 0);
+        for is_white in [true, false] {
+            for role in ROLES {
+                let mut bitboard = pieces(board, is_white, role);
+                while bitboard.0 != 0 {
+                    let square = bitboard.0.trailing_zeros() as usize;
+                    bitboard.0 = bitboard.0.blsr();
+                    self.add(values, feature(perspective, king, is_white, role, square));
+                }
+            }
+        }
+        accumulators.network = self.id;
+    }
+
+    pub fn accumulate(&self, board: &Board) -> Accumulators {
+        let mut accumulators = Accumulators::empty();
+        self.refresh(&mut accumulators, board, 0);
+        self.refresh(&mut accumulators, board, 1);
+        accumulators
+    }
+
+    // from the side to move's point of view, in centipawns
+    pub fn evaluate(&self, accumulators: &Accumulators, board: &Board, is_white: bool) -> i32 {
+        // accumulators built before this network was loaded
+        let fresh;
+        let accumulators = if accumulators.network == self.id {
+            accumulators
+        } else {
+            fresh = self.accumulate(board);
+            &fresh
+        };
+
+        let (us, them) = if is_white { (0, 1) } else { (1, 0) };
+        let mut sum: i64 = 0;
+        for (values, weights) in [
+            (&accumulators.values[us], &self.output_weights[..HIDDEN]),
+            (&accumulators.values[them], &self.output_weights[HIDDEN..]),
+        ] {
+            for (value, weight) in values.iter().zip(weights) {
+                sum += ((*value as i32).clamp(0, QA) * *weight as i32) as i64;
+            }
+        }
+
+        // the clipped hidden values are in QA units and the output weights in QB units
+        ((sum + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+// hidden layer of both perspectives, white first
+#[derive(Debug, Copy, Clone)]
+pub struct Accumulators {
+    values: [[i16; HIDDEN]; 2],
+    // the network the values were computed with, 0 for none
+    network: usize,
+}
+
+impl Accumulators {
+    fn empty() -> Accumulators {
+        Accumulators {
+            values: [[0; HIDDEN]; 2],
+            network: 0,
+        }
+    }
+
+    pub fn new(board: &Board) -> Accumulators {
+        match network() {
+            Some(network) => network.accumulate(board),
+            None => Accumulators::empty(),
+        }
+    }
+
+    // applies the difference between two boards, a side whose king moved is refreshed
+    pub fn update(&mut self, network: &Network, before: &Board, after: &Board) {
+        if self.network != network.id {
+            *self = network.accumulate(after);
+            return;
+        }
+
+        for perspective in 0..2 {
+            let king = king_square(after, perspective == 0);
+            if king != king_square(before, perspective == 0) {
+                network.refresh(self, after, perspective);
+                continue;
+            }
+
+            for is_white in [true, false] {
+                for role in ROLES {
+                    let old = pieces(before, is_white, role);
+                    let new = pieces(after, is_white, role);
+                    let mut removed = old & !new;
+                    while removed.0 != 0 {
+                        let square = removed.0.trailing_zeros() as usize;
+                        removed.0 = removed.0.blsr();
+                        let index = feature(perspective, king, is_white, role, square);
+                        network.sub(&mut self.values[perspective], index);
+                    }
+                    let mut added = new & !old;
+                    while added.0 != 0 {
+                        let square = added.0.trailing_zeros() as usize;
+                        added.0 = added.0.blsr();
+                        let index = feature(perspective, king, is_white, role, square);
+                        network.add(&mut self.values[perspective], index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use rand::{Rng, SeedableRng};
+
+    fn random_network() -> Network {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let count = INPUTS * HIDDEN + HIDDEN + 2 * HIDDEN + 1;
+        let bytes: Vec<u8> = (0..count)
+            .flat_map(|_| rng.gen_range(-64i16..64).to_le_bytes())
+            .collect();
+        Network::from_bytes(&bytes).expect("invalid network")
+    }
+
+    #[test]
+    fn output_is_in_centipawns() {
+        // one hidden unit of the side to move is fully on and weighted 1.0, half a unit
+        // of bias on top: one and a half times the scale
+        let mut output_weights = vec![0; 2 * HIDDEN];
+        output_weights[0] = QB as i16;
+        let mut feature_bias = vec![0; HIDDEN];
+        feature_bias[0] = QA as i16;
+        let network = Network {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            feature_weights: vec![0; INPUTS * HIDDEN],
+            feature_bias,
+            output_weights,
+            output_bias: (QA * QB / 2) as i16,
+        };
+        let board = Board::new();
+        let accumulators = network.accumulate(&board);
+        assert_eq!(network.evaluate(&accumulators, &board, true), SCALE * 3 / 2);
+    }
+
+    #[test]
+    fn rejects_wrong_sizes() {
+        assert!(Network::from_bytes(&[0; 1024]).is_err());
+    }
+
+    #[test]
+    fn incremental_matches_refresh() {
+        let network = random_network();
+        let mut game = Game::from_fen("r3k2r/1P6/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1").expect("invalid fen");
+        let mut accumulators = network.accumulate(&game.board);
+
+        // en passant, promotions, castling, captures and king moves
+        for uci in ["e2e4", "d4e3", "b7a8n", "e8g8", "e1c1", "e3e2", "d1d8", "f8d8", "h1h2", "e2e1q"] {
+            let before = game.board;
+            game.play_uci(uci).expect("illegal move");
+            accumulators.update(&network, &before, &game.board);
+            assert_eq!(
+                accumulators.values,
+                network.accumulate(&game.board).values,
+                "after {}",
+                uci
+            );
+        }
+    }
+
+    #[test]
+    fn mirrored_positions_are_equal() {
+        let network = random_network();
+        let white = Game::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4")
+            .expect("invalid fen");
+        let black = Game::from_fen("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4")
+            .expect("invalid fen");
+        assert_eq!(
+            network.evaluate(&network.accumulate(&white.board), &white.board, true),
+            network.evaluate(&network.accumulate(&black.board), &black.board, false)
+        );
+    }
+}
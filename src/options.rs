@@ -0,0 +1,334 @@
+use std::fmt::Display;
+
+use crate::tt;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum OptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Combo {
+        default: &'static str,
+        vars: &'static [&'static str],
+    },
+    String {
+        default: &'static str,
+    },
+    Button,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionValue {
+    Check(bool),
+    Spin(i64),
+    String(String),
+    Button,
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineOption {
+    pub name: &'static str,
+    pub kind: OptionKind,
+    pub value: OptionValue,
+}
+
+impl EngineOption {
+    fn new(name: &'static str, kind: OptionKind) -> EngineOption {
+        let value = match kind {
+            OptionKind::Check { default } => OptionValue::Check(default),
+            OptionKind::Spin { default, .. } => OptionValue::Spin(default),
+            OptionKind::Combo { default, .. } => OptionValue::String(default.to_string()),
+            OptionKind::String { default } => OptionValue::String(default.to_string()),
+            OptionKind::Button => OptionValue::Button,
+        };
+
+        EngineOption { name, kind, value }
+    }
+
+    fn parse_value(&self, value: Option<&str>) -> Result<OptionValue, &'static str> {
+        match self.kind {
+            OptionKind::Button => Ok(OptionValue::Button),
+            OptionKind::Check { .. } => match value.map(|v| v.to_ascii_lowercase()).as_deref() {
+                Some("true") => Ok(OptionValue::Check(true)),
+                Some("false") => Ok(OptionValue::Check(false)),
+                Some(_) => Err("value has to be true or false"),
+                None => Err("missing value"),
+            },
+            OptionKind::Spin { min, max, .. } => {
+                let value = value.ok_or("missing value")?;
+                let value = value.parse::<i64>().map_err(|_| "value is not a number")?;
+                if value < min || value > max {
+                    return Err("value out of range");
+                }
+                Ok(OptionValue::Spin(value))
+            }
+            OptionKind::Combo { vars, .. } => {
+                let value = value.ok_or("missing value")?;
+                vars.iter()
+                    .find(|var| var.eq_ignore_ascii_case(value))
+                    .map(|var| OptionValue::String(var.to_string()))
+                    .ok_or("value is not one of the combo vars")
+            }
+            OptionKind::String { .. } => match value {
+                None | Some("<empty>") => Ok(OptionValue::String(String::new())),
+                Some(value) => Ok(OptionValue::String(value.to_string())),
+            },
+        }
+    }
+}
+
+impl Display for EngineOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match self.kind {
+            OptionKind::Check { default } => write!(f, "check default {}", default),
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            OptionKind::Combo { default, vars } => {
+                write!(f, "combo default {}", default)?;
+                for var in vars {
+                    write!(f, " var {}", var)?;
+                }
+                Ok(())
+            }
+            OptionKind::String { default: "" } => write!(f, "string default <empty>"),
+            OptionKind::String { default } => write!(f, "string default {}", default),
+            OptionKind::Button => write!(f, "button"),
+        }
+    }
+}
+
+// the options the engine reads, one enum per kind. Every variant is registered with
+// its kind, so a lookup can neither name a missing option nor one of another kind.
+#[derive(Debug, Clone, Copy)]
+pub enum Spin {
+    Hash,
+    Threads,
+    MultiPv,
+    MoveOverhead,
+}
+
+impl Spin {
+    const ALL: [Spin; 4] = [Spin::Hash, Spin::Threads, Spin::MultiPv, Spin::MoveOverhead];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Spin::Hash => "Hash",
+            Spin::Threads => "Threads",
+            Spin::MultiPv => "MultiPV",
+            Spin::MoveOverhead => "Move Overhead",
+        }
+    }
+
+    fn kind(self) -> OptionKind {
+        let (default, min, max) = match self {
+            Spin::Hash => (tt::DEFAULT_SIZE_MB as i64, 1, 4096),
+            Spin::Threads => (1, 1, 256),
+            Spin::MultiPv => (1, 1, 256),
+            Spin::MoveOverhead => (10, 0, 5000),
+        };
+        OptionKind::Spin { default, min, max }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Check {
+    Ponder,
+    // search features, switchable to measure each of them
+    NullMove,
+    Lmr,
+    ReverseFutility,
+    Futility,
+    LateMovePruning,
+}
+
+impl Check {
+    const ALL: [Check; 6] = [
+        Check::Ponder,
+        Check::NullMove,
+        Check::Lmr,
+        Check::ReverseFutility,
+        Check::Futility,
+        Check::LateMovePruning,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::Ponder => "Ponder",
+            Check::NullMove => "NullMove",
+            Check::Lmr => "LMR",
+            Check::ReverseFutility => "ReverseFutility",
+            Check::Futility => "Futility",
+            Check::LateMovePruning => "LateMovePruning",
+        }
+    }
+
+    fn kind(self) -> OptionKind {
+        OptionKind::Check {
+            default: !matches!(self, Check::Ponder),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum Text {
+    #[cfg(feature = "nnue")]
+    EvalFile,
+}
+
+#[allow(dead_code)]
+impl Text {
+    #[cfg(feature = "nnue")]
+    const ALL: [Text; 1] = [Text::EvalFile];
+    #[cfg(not(feature = "nnue"))]
+    const ALL: [Text; 0] = [];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "nnue")]
+            Text::EvalFile => "EvalFile",
+        }
+    }
+
+    fn kind(self) -> OptionKind {
+        OptionKind::String { default: "" }
+    }
+}
+
+pub const CLEAR_HASH: &str = "Clear Hash";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    options: Vec<EngineOption>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        let spins = Spin::ALL.map(|spin| EngineOption::new(spin.name(), spin.kind()));
+        let checks = Check::ALL.map(|check| EngineOption::new(check.name(), check.kind()));
+        let texts = Text::ALL.map(|text| EngineOption::new(text.name(), text.kind()));
+        let mut options = Vec::new();
+        options.extend(spins);
+        options.push(EngineOption::new(CLEAR_HASH, OptionKind::Button));
+        options.extend(checks);
+        options.extend(texts);
+        Options { options }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &EngineOption> {
+        self.options.iter()
+    }
+
+    // registered by `new` for every variant of the lookups below
+    fn value(&self, name: &str) -> &OptionValue {
+        &self
+            .options
+            .iter()
+            .find(|option| option.name == name)
+            .expect("every option is registered")
+            .value
+    }
+
+    // returns the declared name of the option, so buttons can be acted upon
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<&'static str, &'static str> {
+        let option = self
+            .options
+            .iter_mut()
+            .find(|option| option.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or("no such option")?;
+
+        option.value = option.parse_value(value.map(str::trim))?;
+        Ok(option.name)
+    }
+
+    pub fn spin(&self, option: Spin) -> i64 {
+        match self.value(option.name()) {
+            OptionValue::Spin(value) => *value,
+            _ => unreachable!("{} is registered as a spin option", option.name()),
+        }
+    }
+
+    pub fn check(&self, option: Check) -> bool {
+        match self.value(option.name()) {
+            OptionValue::Check(value) => *value,
+            _ => unreachable!("{} is registered as a check option", option.name()),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn string(&self, option: Text) -> &str {
+        match self.value(option.name()) {
+            OptionValue::String(value) => value,
+            _ => unreachable!("{} is registered as a string option", option.name()),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_is_case_insensitive() {
+        let mut options = Options::new();
+        assert_eq!(options.set("move overhead", Some("100")), Ok("Move Overhead"));
+        assert_eq!(options.spin(Spin::MoveOverhead), 100);
+
+        assert_eq!(options.set("PONDER", Some("True")), Ok("Ponder"));
+        assert!(options.check(Check::Ponder));
+    }
+
+    #[test]
+    fn set_validates_values() {
+        let mut options = Options::new();
+        assert!(options.set("Hash", Some("0")).is_err());
+        assert!(options.set("Hash", Some("lots")).is_err());
+        assert!(options.set("Hash", None).is_err());
+        assert!(options.set("Ponder", Some("maybe")).is_err());
+        assert!(options.set("Nonexistent", Some("1")).is_err());
+        assert_eq!(options.spin(Spin::Hash), tt::DEFAULT_SIZE_MB as i64);
+    }
+
+    #[test]
+    fn every_lookup_is_registered() {
+        let options = Options::new();
+        for spin in Spin::ALL {
+            assert!(matches!(spin.kind(), OptionKind::Spin { default, .. } if default == options.spin(spin)));
+        }
+        for check in Check::ALL {
+            assert!(matches!(check.kind(), OptionKind::Check { default } if default == options.check(check)));
+        }
+        for text in Text::ALL {
+            assert_eq!(options.string(text), "");
+        }
+    }
+
+    #[test]
+    fn buttons_need_no_value() {
+        let mut options = Options::new();
+        assert_eq!(options.set("clear hash", None), Ok("Clear Hash"));
+    }
+
+    #[test]
+    fn option_lines() {
+        let options = Options::new();
+        let lines: Vec<String> = options.iter().map(|option| option.to_string()).collect();
+        assert!(lines.contains(&"option name Hash type spin default 16 min 1 max 4096".to_string()));
+        assert!(lines.contains(&"option name Clear Hash type button".to_string()));
+        assert!(lines.contains(&"option name Ponder type check default false".to_string()));
+    }
+}
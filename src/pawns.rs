@@ -0,0 +1,218 @@
+use crate::bitboard::Bitboard;
+use crate::bmi::Bmi;
+use crate::board::Board;
+use crate::eval::PARAMS;
+
+// entries in the pawn hash table, a power of two
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+#[derive(Debug, Copy, Clone, Default)]
+struct PawnEntry {
+    key: u64,
+    mg: i32,
+    eg: i32,
+}
+
+// the pawn structure changes rarely, so its score is cached by the pawn key.
+// An empty entry has key 0, which is also the key and score of a board without pawns.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    pub fn new() -> PawnTable {
+        PawnTable {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+        }
+    }
+
+    // middlegame and endgame pawn structure score from white's point of view
+    pub fn probe(&mut self, board: &Board) -> (i32, i32) {
+        let key = board.pawn_zobrist;
+        let entry = &mut self.entries[key as usize & (PAWN_TABLE_SIZE - 1)];
+        if entry.key != key {
+            let (mg, eg) = evaluate_pawns(board);
+            *entry = PawnEntry { key, mg, eg };
+        }
+        (entry.mg, entry.eg)
+    }
+}
+
+fn adjacent_files(file: usize) -> Bitboard {
+    let mut files = Bitboard(0);
+    if file > 0 {
+        files |= Bitboard::from_file_number(file - 1);
+    }
+    if file < 7 {
+        files |= Bitboard::from_file_number(file + 1);
+    }
+    files
+}
+
+// all ranks in front of `rank` as seen from the given side
+fn ranks_ahead(is_white: bool, rank: usize) -> Bitboard {
+    let mut ranks = Bitboard(0);
+    let ahead = if is_white { rank + 1..8 } else { 0..rank };
+    for r in ahead {
+        ranks |= Bitboard::from_rank_number(r);
+    }
+    ranks
+}
+
+// pawn structure score from white's point of view, computed from scratch
+pub fn evaluate_pawns(board: &Board) -> (i32, i32) {
+    let (white_mg, white_eg) = evaluate_side(board, true);
+    let (black_mg, black_eg) = evaluate_side(board, false);
+    (white_mg - black_mg, white_eg - black_eg)
+}
+
+// how often each pawn structure term occurs for one side
+#[derive(Debug, Default)]
+pub struct PawnFeatures {
+    // by the rank relative to the pawn's side
+    pub passed: [i32; 8],
+    pub connected: [i32; 8],
+    pub doubled: i32,
+    pub isolated: i32,
+    pub backward: i32,
+}
+
+// no enemy pawn can stop or capture the pawn on `square` on its way
+pub fn is_passed(board: &Board, is_white: bool, square: usize) -> bool {
+    let enemy = board.by_role.pawns & board.enemy_bitboard(is_white);
+    let file = square % 8;
+    let blockers = Bitboard::from_file_number(file) | adjacent_files(file);
+    enemy & blockers & ranks_ahead(is_white, square / 8) == Bitboard(0)
+}
+
+pub fn features(board: &Board, is_white: bool) -> PawnFeatures {
+    let own = board.by_role.pawns & board.my_bitboard(is_white);
+    let enemy = board.by_role.pawns & board.enemy_bitboard(is_white);
+    let mut features = PawnFeatures::default();
+
+    let mut pawns = own;
+    while pawns.0 != 0 {
+        let square = pawns.0.trailing_zeros() as usize;
+        pawns.0 = pawns.0.blsr();
+
+        let file = square % 8;
+        let rank = square / 8;
+        let relative_rank = if is_white { rank } else { 7 - rank };
+        let file_mask = Bitboard::from_file_number(file);
+        let adjacent = adjacent_files(file);
+        let ahead = ranks_ahead(is_white, rank);
+
+        if is_passed(board, is_white, square) {
+            features.passed[relative_rank] += 1;
+        }
+
+        // only the rear pawn of a doubled pair is counted
+        if own & file_mask & ahead != Bitboard(0) {
+            features.doubled += 1;
+        }
+
+        if own & adjacent == Bitboard(0) {
+            features.isolated += 1;
+            continue;
+        }
+
+        // side by side or protected by a pawn from behind
+        let behind = if is_white { rank - 1 } else { rank + 1 };
+        let neighbours = Bitboard::from_rank_number(rank) | Bitboard::from_rank_number(behind);
+        if own & adjacent & neighbours != Bitboard(0) {
+            features.connected[relative_rank] += 1;
+            continue;
+        }
+
+        // every neighbour has advanced past it and an enemy pawn guards the square in front
+        if own & adjacent & !ahead == Bitboard(0) && relative_rank < 6 {
+            let stop = if is_white { square + 8 } else { square - 8 };
+            if Board::pawn_attacks(is_white, Bitboard(1 << stop)) & enemy != Bitboard(0) {
+                features.backward += 1;
+            }
+        }
+    }
+
+    features
+}
+
+// pawn structure score of one side
+pub fn evaluate_side(board: &Board, is_white: bool) -> (i32, i32) {
+    let features = features(board, is_white);
+    let mut mg = 0;
+    let mut eg = 0;
+    for (counts, weights) in [
+        (&features.passed, &PARAMS.passed_pawn),
+        (&features.connected, &PARAMS.connected_pawn),
+    ] {
+        for (count, weight) in counts.iter().zip(weights) {
+            mg += count * weight.0;
+            eg += count * weight.1;
+        }
+    }
+    for (count, weight) in [
+        (features.doubled, PARAMS.doubled_pawn),
+        (features.isolated, PARAMS.isolated_pawn),
+        (features.backward, PARAMS.backward_pawn),
+    ] {
+        mg += count * weight.0;
+        eg += count * weight.1;
+    }
+
+    (mg, eg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white_terms(fen: &str) -> (i32, i32) {
+        evaluate_side(&Board::from_fen(fen), true)
+    }
+
+    #[test]
+    fn passed_pawns() {
+        // the e-pawn is stopped by the f-pawn, the a-pawn is free
+        let (mg, eg) = white_terms("4k3/5p2/8/8/P3P3/8/8/4K3 w - - 0 1");
+        assert_eq!(mg, PARAMS.passed_pawn[3].0 + 2 * PARAMS.isolated_pawn.0);
+        assert_eq!(eg, PARAMS.passed_pawn[3].1 + 2 * PARAMS.isolated_pawn.1);
+    }
+
+    #[test]
+    fn doubled_and_isolated_pawns() {
+        let (mg, eg) = white_terms("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1");
+        assert_eq!(mg, PARAMS.doubled_pawn.0 + 2 * PARAMS.isolated_pawn.0 + PARAMS.passed_pawn[2].0 + PARAMS.passed_pawn[1].0);
+        assert_eq!(eg, PARAMS.doubled_pawn.1 + 2 * PARAMS.isolated_pawn.1 + PARAMS.passed_pawn[2].1 + PARAMS.passed_pawn[1].1);
+    }
+
+    #[test]
+    fn connected_and_backward_pawns() {
+        // d4 is protected by e3, c2 is left behind with c3 covered by the b4 pawn
+        let (mg, eg) = white_terms("4k3/8/8/8/1p1P4/4P3/2P5/4K3 w - - 0 1");
+        assert_eq!(mg, PARAMS.backward_pawn.0 + PARAMS.connected_pawn[3].0 + PARAMS.passed_pawn[3].0 + PARAMS.passed_pawn[2].0);
+        assert_eq!(eg, PARAMS.backward_pawn.1 + PARAMS.connected_pawn[3].1 + PARAMS.passed_pawn[3].1 + PARAMS.passed_pawn[2].1);
+    }
+
+    #[test]
+    fn pawn_key_is_incremental() {
+        let mut game = crate::game::Game::from_fen("4k3/1p6/8/2P5/8/8/4P3/4K3 w - - 0 1").expect("invalid fen");
+        for uci in ["e2e4", "b7b5", "c5b6", "e8d7", "b6b7", "d7c7", "b7b8q"] {
+            game.play_uci(uci).expect("illegal move");
+            assert_eq!(game.board.pawn_zobrist, game.board.pawn_key(), "after {}", uci);
+        }
+
+        // kings and queens don't change the key
+        let a = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        let b = Board::from_fen("3qk3/8/8/8/8/8/4P3/3K4 w - - 0 1");
+        assert_eq!(a.pawn_zobrist, b.pawn_zobrist);
+    }
+
+    #[test]
+    fn table_matches_evaluation() {
+        let mut table = PawnTable::new();
+        let board = Board::from_fen("4k3/pp3p2/8/3p4/8/2P5/PP3PP1/4K3 w - - 0 1");
+        assert_eq!(table.probe(&board), evaluate_pawns(&board));
+        assert_eq!(table.probe(&board), evaluate_pawns(&board));
+        assert_eq!(table.probe(&Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1")), (0, 0));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::values::*;
+
 #[derive(Debug, Copy, Clone)]
 pub enum Role {
     Pawn = 0,
@@ -9,6 +11,17 @@ pub enum Role {
 }
 
 impl Role {
+    pub const fn value(self) -> i32 {
+        match self {
+            Role::Pawn => PAWN_VALUE,
+            Role::Bishop => BISHOP_VALUE,
+            Role::Knight => KNIGHT_VALUE,
+            Role::Rook => ROOK_VALUE,
+            Role::Queen => QUEEN_VALUE,
+            Role::King => KING_VALUE,
+        }
+    }
+
     pub fn from_char(c: char) -> Role {
         match c {
             'P' | 'p' => Role::Pawn,
@@ -28,3 +41,14 @@ pub enum PromotionRole {
     Bishop = 2,
     Knight = 3,
 }
+
+impl PromotionRole {
+    pub const fn role(&self) -> Role {
+        match self {
+            PromotionRole::Queen => Role::Queen,
+            PromotionRole::Rook => Role::Rook,
+            PromotionRole::Bishop => Role::Bishop,
+            PromotionRole::Knight => Role::Knight,
+        }
+    }
+}
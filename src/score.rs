@@ -8,6 +8,27 @@ pub enum Score {
     CP(i32),
 }
 
+impl Score {
+    // the bounds of every search result: the earliest mate is on the first ply,
+    // and only the root's opponent can be mated there
+    pub const LOWEST: Score = Score::MateIn(-1);
+    pub const HIGHEST: Score = Score::MateIn(1);
+
+    // the smallest score above this one, the upper bound of a null window
+    pub fn successor(self) -> Score {
+        match self {
+            Score::CP(cp) => Score::CP(cp + 1),
+            Score::MateIn(mate) if mate > 1 => Score::MateIn(mate - 1),
+            Score::MateIn(mate) if mate >= 0 => self,
+            Score::MateIn(mate) => Score::MateIn(mate - 1),
+        }
+    }
+
+    pub fn is_mate(self) -> bool {
+        matches!(self, Score::MateIn(_))
+    }
+}
+
 impl PartialEq for Score {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -125,4 +146,19 @@ mod tests {
         let b = Score::MateIn(15);
         assert!(a > b);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn mates_are_outside_every_cp() {
+        assert!(Score::LOWEST < Score::MateIn(-40));
+        assert!(Score::MateIn(-40) < Score::CP(-100_000));
+        assert!(Score::HIGHEST > Score::MateIn(40));
+    }
+
+    #[test]
+    fn successor_is_the_next_score() {
+        assert_eq!(Score::CP(-3).successor(), Score::CP(-2));
+        assert_eq!(Score::MateIn(3).successor(), Score::MateIn(2));
+        assert_eq!(Score::MateIn(-3).successor(), Score::MateIn(-4));
+        assert!(Score::CP(i32::MAX / 2).successor() < Score::MateIn(40));
+    }
+}
@@ -1,254 +1,680 @@
-use hashbrown::HashMap;
 use std::fmt::Display;
 use std::ops::Neg;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
 use std::time::Instant;
 use std::usize;
-use std::time::Duration;
-use crate::bmi::Bmi;
 use crate::board::Board;
-use crate::game::Outcome;
-use crate::{game::Game, r#move::Move, uci::TimeControl};
-use crate::r#move::{MoveType, Square};
+use crate::eval;
+use crate::{game::Game, r#move::Move, uci::SearchLimits};
+use crate::r#move::MoveType;
+use crate::movepick::{captured_value, is_quiet, mvv_lva, History, MovePicker, HISTORY_MAX};
+use crate::pawns::{self, PawnTable};
 use crate::role::Role;
+use crate::options::{Check, Options, Spin};
 use crate::score::Score;
+use crate::time::TimeManager;
+use crate::tt::{score_from_tt, score_to_tt, NodeType, TTEntry, TranspositionTable};
+use crate::uci::{Info, SendUCI};
 use crate::values::*;
 
 
-pub struct TranspositionTable {
-    table: HashMap<u64, TTEntry>,
+const MAX_PLY: usize = MAX_DEPTH as usize + 1;
+
+// flags the uci loop uses to control a running search
+#[derive(Default)]
+pub struct Signals {
+    pub stop: AtomicBool,
+    pub ponder: AtomicBool,
+}
+
+// triangular table, row `ply` holds the best line found from that ply on
+struct PvTable {
+    moves: [[Move; MAX_PLY]; MAX_PLY],
+    length: [usize; MAX_PLY],
 }
 
-impl TranspositionTable {
-    pub fn new() -> Self {
-        Self {
-            table: HashMap::new(),
+impl PvTable {
+    fn new() -> PvTable {
+        PvTable {
+            moves: [[Move::null(); MAX_PLY]; MAX_PLY],
+            length: [0; MAX_PLY],
         }
     }
 
-    pub fn get(&self, key: u64) -> Option<TTEntry> {
-        self.table.get(&key).copied()
+    fn clear(&mut self, ply: usize) {
+        self.length[ply] = ply;
     }
 
-    pub fn insert(&mut self, key: u64, value: TTEntry) {
-        self.table.insert(key, value);
+    fn update(&mut self, ply: usize, best_move: Move) {
+        self.moves[ply][ply] = best_move;
+        let child_length = if ply + 1 < MAX_PLY { self.length[ply + 1] } else { 0 };
+        for i in ply + 1..child_length {
+            self.moves[ply][i] = self.moves[ply + 1][i];
+        }
+        self.length[ply] = child_length.max(ply + 1);
+    }
+
+    fn line(&self, ply: usize) -> &[Move] {
+        &self.moves[ply][ply..self.length[ply]]
     }
 }
 
-#[derive(Copy, Clone)]
-enum NodeType {
-    Cut,
-    All,
-    PV,
+// node counts are published to the other threads in batches of this size
+const NODE_BATCH: u64 = 1024;
+// reading the clock at every node costs more than it is worth, a few
+// thousand nodes are well below a millisecond
+const TIME_CHECK_NODES: u64 = 1024;
+
+// what the threads of one search share besides the transposition table
+struct Shared {
+    // nodes searched by each thread, the main thread first
+    nodes: Vec<AtomicU64>,
+    // set when the main thread is done, the helpers stop with it
+    done: AtomicBool,
 }
 
-#[derive(Copy, Clone)]
-pub struct TTEntry {
-    score: Score,
-    depth: u32,
-    node_type: NodeType,
-    best_move: Move,
+impl Shared {
+    fn new(threads: usize) -> Shared {
+        Shared {
+            nodes: (0..threads).map(|_| AtomicU64::new(0)).collect(),
+            done: AtomicBool::new(false),
+        }
+    }
+}
+
+// helper threads leave out some depths, so that they are spread over more
+// iterations than the main thread's
+const SKIP_SIZE: [u32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [u32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+fn skips_depth(thread: usize, depth: u32) -> bool {
+    if thread == 0 {
+        return false;
+    }
+    let i = (thread - 1) % SKIP_SIZE.len();
+    (depth + SKIP_PHASE[i]) / SKIP_SIZE[i] % 2 == 1
+}
+
+// state shared by all nodes of one search thread
+pub struct SearchContext<'a> {
+    tt: &'a TranspositionTable,
+    signals: &'a Signals,
+    shared: &'a Shared,
+    // 0 for the main thread, which reports and decides on the best move
+    thread: usize,
+    start: Instant,
+    // restarted on ponderhit, the time limits only apply after it
+    pondering: bool,
+    time: TimeManager,
+    // the clock is read once every TIME_CHECK_NODES nodes, the result is kept
+    next_time_check: u64,
+    stopped: bool,
+    nodes: u64,
+    max_nodes: Option<u64>,
+    seldepth: usize,
+    pv: PvTable,
+    pawn_table: PawnTable,
+    pruning: Pruning,
+    // reduction by depth and move index, see `reduction`
+    reductions: [[u32; 64]; 64],
+    history: History,
+    // two quiet moves that caused a cutoff at each ply, the latest first
+    killers: [[Move; 2]; MAX_PLY],
+    // the move played at each ply of the current line, null for a null move
+    played: [Move; MAX_PLY],
+    // the move left out by the singular extension search at each ply
+    excluded: [Move; MAX_PLY],
+    // depth of the current iteration, extensions stop at twice as many plies
+    root_depth: u32,
+}
+
+impl<'a> SearchContext<'a> {
+    fn new(
+        tt: &'a TranspositionTable,
+        signals: &'a Signals,
+        shared: &'a Shared,
+        thread: usize,
+        options: &Options,
+    ) -> SearchContext<'a> {
+        SearchContext {
+            tt,
+            signals,
+            shared,
+            thread,
+            start: Instant::now(),
+            pondering: signals.ponder.load(Ordering::Relaxed),
+            time: TimeManager::infinite(),
+            next_time_check: 0,
+            stopped: false,
+            nodes: 0,
+            max_nodes: None,
+            seldepth: 0,
+            pv: PvTable::new(),
+            pawn_table: PawnTable::new(),
+            pruning: Pruning::from_options(options),
+            reductions: reduction_table(),
+            history: History::new(),
+            killers: [[Move::null(); 2]; MAX_PLY],
+            played: [Move::null(); MAX_PLY],
+            excluded: [Move::null(); MAX_PLY],
+            root_depth: 0,
+        }
+    }
+
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.nodes % NODE_BATCH == 0 {
+            self.shared.nodes[self.thread].store(self.nodes, Ordering::Relaxed);
+        }
+    }
+
+    // the exact count of this thread and the last published counts of the others
+    fn total_nodes(&self) -> u64 {
+        let others: u64 = self
+            .shared
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|&(thread, _)| thread != self.thread)
+            .map(|(_, nodes)| nodes.load(Ordering::Relaxed))
+            .sum();
+        self.nodes + others
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = self.limit_reached();
+        }
+        self.stopped
+    }
+
+    fn limit_reached(&mut self) -> bool {
+        if self.signals.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.thread != 0 {
+            return self.shared.done.load(Ordering::Relaxed);
+        }
+        if self.max_nodes.is_some_and(|max_nodes| self.total_nodes() >= max_nodes) {
+            return true;
+        }
+        // the best move has to come from a finished iteration, however short the time
+        if self.root_depth <= 1 || self.nodes < self.next_time_check {
+            return false;
+        }
+        self.next_time_check = self.nodes + TIME_CHECK_NODES;
+        !self.is_pondering() && self.time.hard_limit_reached()
+    }
+
+    // the time of the move only starts counting once the opponent played the pondered move
+    fn is_pondering(&mut self) -> bool {
+        if self.signals.ponder.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.pondering {
+            self.pondering = false;
+            self.time.restart();
+        }
+        false
+    }
+
+    // between iterations, whether the next one is worth starting
+    fn out_of_time(&mut self) -> bool {
+        self.thread == 0 && !self.is_pondering() && self.time.soft_limit_reached()
+    }
+}
+
+// search features that can be switched off through the uci options
+#[derive(Debug, Copy, Clone)]
+struct Pruning {
+    null_move: bool,
+    lmr: bool,
+    reverse_futility: bool,
+    futility: bool,
+    late_move_pruning: bool,
+}
+
+impl Pruning {
+    fn from_options(options: &Options) -> Pruning {
+        Pruning {
+            null_move: options.check(Check::NullMove),
+            lmr: options.check(Check::Lmr),
+            reverse_futility: options.check(Check::ReverseFutility),
+            futility: options.check(Check::Futility),
+            late_move_pruning: options.check(Check::LateMovePruning),
+        }
+    }
+}
+
+// late moves are searched less deep, the more so the later they come and the deeper we are
+fn reduction_table() -> [[u32; 64]; 64] {
+    let mut table = [[0; 64]; 64];
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (index, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + (depth as f64).ln() * (index as f64).ln() / 2.25) as u32;
+        }
+    }
+    table
 }
 
 pub struct SearchResult {
-    pub best_move: Move,
-    pub best_score: Score,
-    pub time: Duration,
+    // none without a legal move, the game is already over
+    pub best_move: Option<Move>,
+    pub ponder_move: Option<Move>,
 }
 
-pub fn search(game: Game, time_control: TimeControl) -> SearchResult {
-    let start = Instant::now();
-    let (mut moves, count) = game.get_legal_moves();
-    sort_moves(&mut moves, count, game_phase(game.board), None);
-    let mut evaluations: HashMap<u32, Score> = HashMap::new();
-    let mut tt = TranspositionTable::new();
-
-    let time = match time_control {
-        TimeControl::Infinite => 30_000,
-        TimeControl::Movetime(time) => time * 96 / 100,
-        TimeControl::RemainingTime {
-            white,
-            black,
-            winc,
-            binc,
-            movestogo,
-        } => {
-            if game.is_white {
-                winc + (white / (movestogo + 1))
-            } else {
-                binc + (black / (movestogo + 1))
-            }
+// the info line sent for every pv after an iteration and before bestmove
+fn report(context: &SearchContext, depth: u32, multi_pv: usize, score: Score, pv: &[Move]) {
+    let elapsed = context.start.elapsed();
+    let nodes = context.total_nodes();
+    let nps = nodes * 1000 / (elapsed.as_millis() as u64).max(1);
+
+    println!(
+        "{}",
+        SendUCI::InfoLine(vec![
+            Info::Depth(depth as u64),
+            Info::SelDepth(context.seldepth.max(pv.len()) as u64),
+            Info::MultiPV(multi_pv as u64),
+            Info::Score(score.into()),
+            Info::Nodes(nodes),
+            Info::NPS(nps),
+            Info::HashFull(context.tt.hashfull()),
+            Info::Time(elapsed),
+            Info::PV(pv.to_vec()),
+        ])
+        .to_str()
+    );
+}
+
+// a legal move at the root and what the iterations found out about it
+#[derive(Debug, Clone)]
+struct RootMove {
+    m: Move,
+    // exact for the moves that raised alpha, LOWEST for the others, which only
+    // have to rank after them
+    score: Score,
+    // the score lies inside the window it was searched with
+    exact: bool,
+    // the score of the last completed iteration
+    previous_score: Score,
+    pv: Vec<Move>,
+    // spent below the move in the current iteration
+    nodes: u64,
+}
+
+impl RootMove {
+    fn new(m: Move) -> RootMove {
+        RootMove {
+            m,
+            score: Score::LOWEST,
+            exact: false,
+            previous_score: Score::LOWEST,
+            pv: vec![m],
+            nodes: 0,
         }
-    };
+    }
+}
 
-    let mut best_value = Score::CP(MIN);
+// best first, moves with equal scores keep their order from the last iteration
+fn sort_root_moves(moves: &mut [RootMove]) {
+    moves.sort_by(|a, b| b.score.cmp(&a.score));
+}
 
-    let mut alpha = Score::CP(MIN);
-    let beta = Score::MateIn(1);
+// the best `multi_pv` root moves in rank order, `moves` has to be sorted
+fn report_lines(context: &SearchContext, depth: u32, moves: &[RootMove], multi_pv: usize) {
+    if context.thread != 0 {
+        return;
+    }
+    for (i, root_move) in moves.iter().take(multi_pv).enumerate() {
+        report(context, depth, i + 1, root_move.score, &root_move.pv);
+    }
+}
 
-    let mut depth = 1;
-    while start.elapsed() < Duration::from_millis(time) && depth <= MAX_DEPTH {
-        for i in 0..count {
-            if moves[i].0 == 0 {
-                continue;
-            }
-            let mut new_game = game;
-            new_game.play(moves[i]);
-            let mut value = -negamax(
-                new_game,
-                depth - 1,
-                depth,
-                -beta,
-                -alpha,
-                &mut tt,
-                start,
-                time,
-            );
-            evaluations.insert(moves[i].0, value);
+// the expected reply to our best move, from the principal variation or the table
+fn ponder_move(game: &mut Game, pv: &[Move], tt: &TranspositionTable, history: &History) -> Option<Move> {
+    if let Some(&reply) = pv.get(1) {
+        return Some(reply);
+    }
 
-            if value < best_value {
-                continue;
-            }
-            if value < beta && depth > 2 {
-                value = -negamax(
-                    new_game,
-                    depth - 1,
-                    depth,
-                    -beta,
-                    -value,
-                    &mut tt,
-                    start,
-                    time,
-                );
-                evaluations.insert(moves[i].0, value);
-            }
+    // otherwise the move ordering's first choice, the table move if it is legal here
+    game.make_move(pv[0]);
+    let (moves, count) = game.get_legal_moves();
+    let tt_move = tt.get(game.board.zobrist).map(|entry| entry.best_move);
+    let mut picker = MovePicker::new(moves, count, tt_move, [Move::null(); 2], Move::null(), [Move::null(); 2]);
+    let reply = picker.next(&game.board, history);
+    game.unmake_move();
+    reply
+}
 
-            if value > best_value {
-                best_value = value;
-            }
+pub fn search(
+    mut game: Game,
+    limits: &SearchLimits,
+    tt: &TranspositionTable,
+    options: &Options,
+    signals: &Signals,
+) -> SearchResult {
+    let (mut moves, mut count) = game.get_legal_moves();
 
-            if value >= beta {
-                break;
-            }
+    let search_moves: Vec<Move> = moves[0..count]
+        .iter()
+        .copied()
+        .filter(|m| limits.search_moves.contains(&m.to_algebraic()))
+        .collect();
+    if !search_moves.is_empty() {
+        count = search_moves.len();
+        moves = [Move::null(); 218];
+        moves[0..count].copy_from_slice(&search_moves);
+    }
 
-            alpha = alpha.max(value);
-
-            // Check if the elapsed time is more than 20 seconds
-            if start.elapsed() > Duration::from_millis(time) {
-                moves.sort_by(|&a, &b| {
-                    evaluations
-                        .get(&b.0)
-                        .unwrap_or(&Score::CP(MIN))
-                        .cmp(evaluations.get(&a.0).unwrap_or(&Score::CP(MIN)))
-                });
-                println!("info timeout");
-                println!("info score cp {}", best_value);
-
-                for i in 0..count {
-                    println!("{}: {}", moves[i], evaluations.get(&moves[i].0).unwrap_or(&Score::CP(MIN)));
-                }
+    let threads = options.spin(Spin::Threads) as usize;
+    let shared = Shared::new(threads);
+    let mut context = SearchContext::new(tt, signals, &shared, 0, options);
+
+    // captures first, the iterations sort the moves by their scores from then on
+    let mut picker = MovePicker::new(moves, count, None, [Move::null(); 2], Move::null(), [Move::null(); 2]);
+    for m in moves[0..count].iter_mut() {
+        *m = picker.next(&game.board, &context.history).expect("fewer moves than counted");
+    }
+    tt.new_search();
+
+    let time = TimeManager::new(limits, game.is_white, options.spin(Spin::MoveOverhead) as u64);
+    let max_depth = limits.depth.map_or(MAX_DEPTH, |depth| depth.clamp(1, MAX_DEPTH));
+
+    let multi_pv = (options.spin(Spin::MultiPv) as usize).clamp(1, count.max(1));
 
-                return SearchResult {
-                    best_move: moves[0],
-                    time: start.elapsed(),
-                    best_score: best_value,
-                };
+    thread::scope(|scope| {
+        for thread in 1..threads {
+            let shared = &shared;
+            let mut game = game.clone();
+            scope.spawn(move || {
+                let mut context = SearchContext::new(tt, signals, shared, thread, options);
+                iterate(&mut game, moves, count, max_depth, 1, limits, &mut context);
+            });
+        }
+
+        context.time = time;
+        context.max_nodes = limits.nodes;
+        let result = iterate(&mut game, moves, count, max_depth, multi_pv, limits, &mut context);
+        shared.done.store(true, Ordering::Relaxed);
+        result
+    })
+}
+
+// iterative deepening of one thread over the root moves
+fn iterate(
+    game: &mut Game,
+    moves: [Move; 218],
+    count: usize,
+    max_depth: u32,
+    multi_pv: usize,
+    limits: &SearchLimits,
+    context: &mut SearchContext,
+) -> SearchResult {
+    let tt = context.tt;
+    let mut root_moves: Vec<RootMove> = moves[0..count].iter().map(|&m| RootMove::new(m)).collect();
+    // iterations the best move has survived
+    let mut stability = 0;
+
+    let mut depth = 1;
+    while !root_moves.is_empty() && !context.should_stop() && depth <= max_depth {
+        if skips_depth(context.thread, depth) {
+            depth += 1;
+            continue;
+        }
+        let last_best = (root_moves[0].m, root_moves[0].score);
+        context.root_depth = depth;
+        context.seldepth = 0;
+        for root_move in root_moves.iter_mut() {
+            root_move.previous_score = root_move.score;
+            root_move.exact = false;
+            root_move.nodes = 0;
+        }
+        let completed = root_moves.clone();
+        let iteration_start = context.nodes;
+
+        // each of the best `multi_pv` lines is searched among the moves not ranked above it yet
+        for pv_index in 0..multi_pv {
+            aspiration(game, &mut root_moves[pv_index..], depth, context);
+            if context.should_stop() {
+                break;
             }
         }
+        // an unfinished iteration isn't reported and the last one's ranking stands, bounds
+        // and placeholders can't be compared with it. Only a move this one scored exactly
+        // takes the lead.
+        if context.should_stop() {
+            let best = root_moves
+                .iter()
+                .filter(|root_move| root_move.exact)
+                .max_by_key(|root_move| root_move.score)
+                .cloned();
+            root_moves = completed;
+            if let Some(best) = best {
+                let index = root_moves
+                    .iter()
+                    .position(|root_move| root_move.m == best.m)
+                    .expect("unknown root move");
+                root_moves.remove(index);
+                root_moves.insert(0, best);
+            }
+            break;
+        }
+        sort_root_moves(&mut root_moves[0..multi_pv]);
+        report_lines(context, depth, &root_moves, multi_pv);
+
         if count == 1 {
             break;
         }
-        println!("info depth {}", depth);
-        println!("info score {}", best_value);
-        println!("info currmove {}", moves[0].to_algebraic());
-        println!("info time {}", start.elapsed().as_millis() as u64);
-        println!("info string ---------------------------------");
-
-        // Sort moves based on evaluations
-        moves.sort_by(|&a, &b| {
-            (evaluations
-                .get(&b.0)
-                .unwrap_or(&Score::CP(MIN)))
-                .cmp(&(evaluations.get(&a.0).unwrap_or(&Score::CP(MIN))))
-        });
 
-        if matches!(best_value, Score::MateIn(x) if x > 0) {
+        let best = &root_moves[0];
+        stability = if best.m == last_best.0 { stability + 1 } else { 0 };
+        let drop = match (last_best.1, best.score) {
+            (Score::CP(last), Score::CP(score)) => last - score,
+            _ => 0,
+        };
+        let share = best.nodes as f64 / (context.nodes - iteration_start).max(1) as f64;
+        context.time.adjust(stability, drop, share);
+        if context.out_of_time() {
             break;
         }
 
+        // with a mate limit, only a mate at least as short as requested ends the search
+        if let Score::MateIn(plies) = best.score {
+            if plies > 0 && limits.mate.map_or(true, |mate| (plies as u32 + 1) / 2 <= mate) {
+                break;
+            }
+        }
+
         depth += 1;
     }
-    moves.sort_by(|&a, &b| {
-        evaluations
-            .get(&b.0)
-            .unwrap_or(&Score::CP(MIN))
-            .cmp(&(evaluations.get(&a.0).unwrap_or(&Score::CP(MIN))))
-    });
 
-    println!("info score {}", best_value);
-
-    for i in 0..count {
-        println!("info string {}: {}", moves[i], evaluations.get(&moves[i].0).unwrap_or(&Score::CP(MIN)));
+    match root_moves.first() {
+        Some(best) => SearchResult {
+            best_move: Some(best.m),
+            ponder_move: ponder_move(game, &best.pv, tt, &context.history),
+        },
+        // mated or stalemated, there is nothing to search
+        None => SearchResult {
+            best_move: None,
+            ponder_move: None,
+        },
     }
+}
 
-    return SearchResult {
-        best_move: moves[0],
-        time: start.elapsed(),
-        best_score: best_value,
+// the window around the last iteration's score starts this wide and grows
+// by half on every failure, until it gives way to the full window
+const ASPIRATION_DEPTH: u32 = 4;
+const ASPIRATION_WINDOW: i32 = 25;
+const ASPIRATION_MAX: i32 = 1000;
+
+// searches `moves[0]`'s line with a narrow window first, the score rarely moves much
+// from one iteration to the next and a narrow window cuts off more
+fn aspiration(game: &mut Game, moves: &mut [RootMove], depth: u32, context: &mut SearchContext) {
+    let mut delta = ASPIRATION_WINDOW;
+    let (mut alpha, mut beta) = match moves[0].previous_score {
+        Score::CP(score) if depth >= ASPIRATION_DEPTH => (Score::CP(score - delta), Score::CP(score + delta)),
+        _ => (Score::LOWEST, Score::HIGHEST),
     };
+
+    loop {
+        let value = search_root(game, moves, depth, alpha, beta, context);
+        sort_root_moves(moves);
+        if context.should_stop() {
+            return;
+        }
+
+        delta += delta / 2;
+        if value <= alpha && alpha != Score::LOWEST {
+            alpha = match value {
+                Score::CP(value) if delta < ASPIRATION_MAX => Score::CP(value - delta),
+                _ => Score::LOWEST,
+            };
+        } else if value >= beta && beta != Score::HIGHEST {
+            beta = match value {
+                Score::CP(value) if delta < ASPIRATION_MAX => Score::CP(value + delta),
+                _ => Score::HIGHEST,
+            };
+        } else {
+            return;
+        }
+    }
+}
+
+// one pass over the root moves, the first one with the full window and the
+// others with a null window first, like every other pv node
+fn search_root(
+    game: &mut Game,
+    moves: &mut [RootMove],
+    depth: u32,
+    mut alpha: Score,
+    beta: Score,
+    context: &mut SearchContext,
+) -> Score {
+    let mut best_value = Score::LOWEST;
+
+    for i in 0..moves.len() {
+        let m = moves[i].m;
+        let nodes = context.nodes;
+        game.make_move(m);
+        context.played[0] = m;
+
+        let mut value = if i == 0 {
+            -negamax(game, depth - 1, 1, -beta, -alpha, context)
+        } else {
+            -negamax(game, depth - 1, 1, -alpha.successor(), -alpha, context)
+        };
+        if i > 0 && value > alpha && value < beta {
+            value = -negamax(game, depth - 1, 1, -beta, -alpha, context);
+        }
+        game.unmake_move();
+        moves[i].nodes += context.nodes - nodes;
+        // the move was cut short, it keeps what the last iteration found
+        if context.should_stop() {
+            return best_value;
+        }
+
+        if i == 0 || value > alpha {
+            moves[i].score = value;
+            moves[i].exact = value > alpha && value < beta;
+            moves[i].pv = vec![m];
+            moves[i].pv.extend_from_slice(context.pv.line(1));
+        } else {
+            moves[i].score = Score::LOWEST;
+            moves[i].exact = false;
+        }
+
+        best_value = best_value.max(value);
+        if value > alpha {
+            alpha = value;
+            if value >= beta {
+                break;
+            }
+        }
+    }
+    best_value
 }
 
+// static evaluation margin per ply of depth for reverse futility pruning
+const REVERSE_FUTILITY_MARGIN: i32 = 90;
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+// margin per ply of depth a quiet move would have to gain to be worth searching
+const FUTILITY_MARGIN: i32 = 120;
+const FUTILITY_DEPTH: u32 = 3;
+const LATE_MOVE_PRUNING_DEPTH: u32 = 4;
+const NULL_MOVE_DEPTH: u32 = 3;
+const LMR_DEPTH: u32 = 3;
+// the tt move, the captures and the best quiets are searched at full depth
+const LMR_MIN_INDEX: usize = 3;
+// the table move is searched one ply deeper when every other move fails well below
+// its score, on a search of half the depth
+const SINGULAR_DEPTH: u32 = 8;
+const SINGULAR_TT_DEPTH: u32 = 3;
+const SINGULAR_MARGIN: i32 = 2;
+
 pub fn negamax(
-    game: Game,
+    game: &mut Game,
     depth: u32,
-    max_depth: u32,
+    ply: usize,
     alpha: Score,
     beta: Score,
-    tt: &mut TranspositionTable,
-    start: Instant,
-    time: u64,
+    context: &mut SearchContext,
 ) -> Score {
-    let mut alpha = alpha;
-    let mut beta = beta;
+    let tt = context.tt;
+    if depth == 0 {
+        return quiescence(game, ply, alpha, beta, context);
+    }
+    context.pv.clear(ply);
+    context.count_node();
+    context.seldepth = context.seldepth.max(ply);
+    // ahead of the table, its entries don't know how the position was reached
+    if game.is_draw(ply) {
+        return Score::CP(-10);
+    }
+    // a null window can only fail high or low, everything else is on the principal variation
+    let pv_node = alpha.successor() != beta;
     let mut best_move: Option<Move> = None;
-    let mut best_value = Score::CP(MIN);
-
-    if let Some(tt_entry) = tt.get(game.board.zobrist) {
-        if tt_entry.depth >= depth {
-            match tt_entry.node_type {
-                NodeType::Cut => {
-                    if beta <= tt_entry.score {
-                        return tt_entry.score;
-                    }
-                    if alpha < tt_entry.score {
-                        alpha = tt_entry.score;
-                    }
-                }
-                NodeType::All => {
-                    if tt_entry.score <= alpha {
-                        return tt_entry.score;
-                    }
-                    if tt_entry.score < beta {
-                        beta = tt_entry.score;
-                    }
-                }
-                NodeType::PV => {
-                    return tt_entry.score;
-                }
-            }
+    let mut best_value = Score::LOWEST;
+
+    // the singular extension search looks at the same position without one of its
+    // moves, its result doesn't belong in the table
+    let excluded = context.excluded[ply];
+    let excluding = excluded != Move::null();
+
+    // the principal variation is always searched, so it is not cut short
+    let tt_entry = tt.get(game.board.zobrist);
+    if let Some(tt_entry) = tt_entry.filter(|entry| !pv_node && !excluding && entry.depth >= depth) {
+        let score = score_from_tt(tt_entry.score, ply);
+        let usable = match tt_entry.node_type {
+            NodeType::PV => true,
+            NodeType::Cut => score >= beta,
+            NodeType::All => score <= alpha,
+        };
+        if usable {
+            return score;
         }
     }
 
-    if depth <= 0 || start.elapsed() > Duration::from_millis(time) {
-        return Score::CP(evaluate(&game));
+    if context.should_stop() || ply >= MAX_PLY - 1 {
+        return Score::CP(evaluate(&game, &mut context.pawn_table));
     }
 
-    let (mut moves, count) = game.get_legal_moves();
-    let tt_best_move = if let Some(tt_entry) = tt.get(game.board.zobrist) {
-        Some(tt_entry.best_move)
-    } else {
-        None
-    };
-    sort_moves(&mut moves, count, game_phase(game.board), tt_best_move);
+    let (moves, count) = game.get_legal_moves();
+    let previous = [
+        if ply >= 1 { context.played[ply - 1] } else { Move::null() },
+        if ply >= 2 { context.played[ply - 2] } else { Move::null() },
+    ];
+    let mut picker = MovePicker::new(
+        moves,
+        count,
+        tt_entry.map(|entry| entry.best_move),
+        context.killers[ply],
+        context.history.countermove(previous[0]),
+        previous,
+    );
+    if ply + 1 < MAX_PLY {
+        context.killers[ply + 1] = [Move::null(); 2];
+    }
 
     if count == 0 {
         let check_mask = game.board.check_mask(game.is_white);
@@ -257,71 +683,225 @@ pub fn negamax(
             return Score::CP(-10);
         }
         // Checkmate
-        return Score::MateIn(-(max_depth as i32 - depth as i32));
-    }
-
-
-    if let Outcome::Draw(_) = game.outcome {
-        return Score::CP(-10);
+        return Score::MateIn(-(ply as i32));
     }
 
     // Skip this position if a mating sequence has already been found earlier in
     // the search, which would be shorter than any mate we could find from here.
-    let mut alpha = alpha.max(Score::MateIn(-(max_depth as i32 - depth as i32)));
-    let mut beta = beta.min(Score::MateIn(max_depth as i32 - depth as i32));
+    let mut alpha = alpha.max(Score::MateIn(-(ply as i32)));
+    let beta = beta.min(Score::MateIn(ply as i32));
     if alpha >= beta {
         return alpha;
     }
 
+    let in_check = game.in_check();
+    let static_eval = if in_check {
+        MIN
+    } else {
+        evaluate(&game, &mut context.pawn_table)
+    };
+    let pruning = context.pruning;
+
+    // far enough above beta that no move is going to bring it back
+    if pruning.reverse_futility
+        && !pv_node
+        && !in_check
+        && depth <= REVERSE_FUTILITY_DEPTH
+        && !beta.is_mate()
+        && Score::CP(static_eval - REVERSE_FUTILITY_MARGIN * depth as i32) >= beta
+    {
+        return Score::CP(static_eval);
+    }
 
-    for i in 0..count {
-        let mut new_game = game.clone();
-        new_game.play(moves[i]);
-        let mut value = -negamax(
-            new_game,
-            depth - 1,
-            max_depth,
+    // if passing still fails high, a real move will too. Not in pawn endgames, where
+    // zugzwang is common, and never twice in a row.
+    let non_pawn_material = game.board.my_bitboard(game.is_white)
+        & !(game.board.by_role.pawns | game.board.by_role.kings);
+    if pruning.null_move
+        && !pv_node
+        && !in_check
+        && depth >= NULL_MOVE_DEPTH
+        && !beta.is_mate()
+        && Score::CP(static_eval) >= beta
+        && non_pawn_material.0 != 0
+        && (ply == 0 || context.played[ply - 1] != Move::null())
+        && !excluding
+    {
+        let reduction = 3 + depth / 4;
+        game.make_null_move();
+        context.played[ply] = Move::null();
+        let value = -negamax(
+            game,
+            depth.saturating_sub(1 + reduction),
+            ply + 1,
             -beta,
-            -alpha,
-            tt,
-            start,
-            time,
+            (-beta).successor(),
+            context,
         );
+        game.unmake_move();
+        if value >= beta {
+            // a mate found after passing is not a real one
+            return if value.is_mate() { beta } else { value };
+        }
+    }
 
-        if value < best_value {
+    // extensions stop once the line is twice as long as the iteration's depth
+    let may_extend = ply < 2 * context.root_depth as usize;
+
+    let mut singular_move = Move::null();
+    if let Some(entry) = tt_entry.filter(|entry| {
+        may_extend
+            && !excluding
+            && depth >= SINGULAR_DEPTH
+            && entry.node_type != NodeType::All
+            && entry.depth + SINGULAR_TT_DEPTH >= depth
+            && moves[0..count].contains(&entry.best_move)
+    }) {
+        if let Score::CP(tt_score) = score_from_tt(entry.score, ply) {
+            let singular_beta = Score::CP(tt_score - SINGULAR_MARGIN * depth as i32);
+            context.excluded[ply] = entry.best_move;
+            let value = negamax(
+                game,
+                (depth - 1) / 2,
+                ply,
+                Score::CP(tt_score - SINGULAR_MARGIN * depth as i32 - 1),
+                singular_beta,
+                context,
+            );
+            context.excluded[ply] = Move::null();
+            if value < singular_beta {
+                singular_move = entry.best_move;
+            } else if singular_beta >= beta {
+                // more than one move beats beta even without the table move
+                return singular_beta;
+            }
+        }
+    }
+
+    let original_alpha = alpha;
+    let mut quiets_tried = [Move::null(); 64];
+    let mut quiet_count = 0;
+
+    for i in 0.. {
+        let Some(m) = picker.next(&game.board, &context.history) else {
+            break;
+        };
+        if m == excluded {
             continue;
         }
-        if value < beta {
-            // Re-search with full depth
-            value = -negamax(
-                new_game,
-                depth - 1,
-                max_depth,
-                -beta,
-                -value,
-                tt,
-                start,
-                time,
-            );
+        let quiet = is_quiet(m);
+        let board = game.board;
+        game.make_move(m);
+        let gives_check = game.in_check();
+
+        // quiet moves late in the list of a node that is not going to raise alpha
+        if !pv_node
+            && !in_check
+            && !gives_check
+            && quiet
+            && i > 0
+            && !(best_value.is_mate() && best_value < Score::CP(0))
+        {
+            if pruning.late_move_pruning
+                && depth <= LATE_MOVE_PRUNING_DEPTH
+                && i >= (3 + depth * depth) as usize
+            {
+                game.unmake_move();
+                continue;
+            }
+            if pruning.futility
+                && depth <= FUTILITY_DEPTH
+                && Score::CP(static_eval + FUTILITY_MARGIN * depth as i32) <= alpha
+            {
+                game.unmake_move();
+                continue;
+            }
         }
 
+        let extension = if may_extend && extends(&board, m, gives_check, singular_move, pv_node, previous[0]) {
+            1
+        } else {
+            0
+        };
+        let new_depth = depth - 1 + extension;
+
+        context.played[ply] = m;
+        let value = if i == 0 {
+            -negamax(game, new_depth, ply + 1, -beta, -alpha, context)
+        } else {
+            let mut reduction = 0;
+            if pruning.lmr && depth >= LMR_DEPTH && i >= LMR_MIN_INDEX && quiet && !in_check && !gives_check {
+                let history = context.history.quiet_score(m, &previous);
+                let r = context.reductions[(depth as usize).min(63)][i.min(63)] as i32
+                    - pv_node as i32
+                    - history / HISTORY_MAX;
+                reduction = r.clamp(0, new_depth as i32 - 1) as u32;
+            }
+
+            // a null window proves the move is worse than the best one so far,
+            // if it isn't, it is searched again without the reduction and the full window
+            let mut value = -negamax(
+                game,
+                new_depth - reduction,
+                ply + 1,
+                -alpha.successor(),
+                -alpha,
+                context,
+            );
+            if value > alpha && reduction > 0 {
+                value = -negamax(game, new_depth, ply + 1, -alpha.successor(), -alpha, context);
+            }
+            if value > alpha && value < beta && pv_node {
+                value = -negamax(game, new_depth, ply + 1, -beta, -alpha, context);
+            }
+            value
+        };
+        game.unmake_move();
+
         if value > best_value {
             best_value = value;
-            best_move = Some(moves[i]);
+            best_move = Some(m);
+            context.pv.update(ply, m);
         }
 
         if best_value >= beta {
-            return best_value;
+            if quiet {
+                context.history.update(m, &quiets_tried[..quiet_count], &previous, depth);
+                let killers = &mut context.killers[ply];
+                if killers[0] != m {
+                    killers[1] = killers[0];
+                    killers[0] = m;
+                }
+            }
+            break;
+        }
+
+        if quiet && quiet_count < quiets_tried.len() {
+            quiets_tried[quiet_count] = m;
+            quiet_count += 1;
         }
 
         alpha = alpha.max(best_value);
     }
 
+    // an interrupted search only knows a part of the moves
+    if context.should_stop() || excluding {
+        return best_value;
+    }
+
+    // fail-soft, a cutoff gives a lower bound and a fail-low an upper bound
+    let node_type = if best_value >= beta {
+        NodeType::Cut
+    } else if best_value > original_alpha {
+        NodeType::PV
+    } else {
+        NodeType::All
+    };
     if let Some(best_move) = best_move {
         tt.insert(game.board.zobrist, TTEntry {
-            score: best_value,
+            score: score_to_tt(best_value, ply),
             depth,
-            node_type: NodeType::PV, // or NodeType::Cut or NodeType::All depending on the situation
+            node_type,
             best_move,
         });
     }
@@ -329,230 +909,200 @@ pub fn negamax(
     return best_value;
 }
 
-// from https://github.com/MitchelPaulin/Walleye
-pub fn evaluate(game: &Game) -> i32 {
-    let mut mg_white = 0;
-    let mut eg_white = 0;
-    let mut mg_black = 0;
-    let mut eg_black = 0;
-    let mut game_phase = 0;
-
-    let mut loop_bitboard = game.board.by_color.white | game.board.by_color.black;
-    while loop_bitboard.0 != 0 {
-        let square_bitboard = loop_bitboard.0.blsi();
-        let square = Square(square_bitboard.trailing_zeros() as u8);
-        let piece = game.board.piece_at(square.0 as i32);
-        if piece.is_none() {
-            loop_bitboard.0 = loop_bitboard.0.blsr();
-            continue;
-        }
-
-        let piece = piece.unwrap();
-
-
-        game_phase += game_phase_val(piece.role);
-        if piece.is_white {
-            mg_white += mg_piece_val(piece.role);
-            eg_white += eg_piece_val(piece.role);
-        } else {
-            mg_black += mg_piece_val(piece.role);
-            eg_black += eg_piece_val(piece.role);
-        }
-
-        loop_bitboard.0 = loop_bitboard.0.blsr();
+// forcing moves are searched a ply deeper, so their point isn't lost at the horizon
+// `board` is the position before the move
+fn extends(
+    board: &Board,
+    m: Move,
+    gives_check: bool,
+    singular_move: Move,
+    pv_node: bool,
+    last: Move,
+) -> bool {
+    if m == singular_move {
+        return true;
     }
-
-    let mg_score = if game.is_white {
-        mg_white - mg_black
-    } else {
-        mg_black - mg_white
-    };
-    let eg_score = if game.is_white {
-        eg_white - eg_black
-    } else {
-        eg_black - eg_white
-    };
-
-    let mut mg_phase = game_phase;
-    mg_phase = mg_phase.min(24);
-
-    let eg_phase = 24 - mg_phase;
-
-    (mg_score * mg_phase + eg_score * eg_phase) / 24
-}
-
-pub fn sort_moves(
-    moves: &mut [Move; 218],
-    count: usize,
-    phase: i32,
-    tt_best_move: Option<Move>,
-) {
-    let mut evaluated_moves: Vec<(Move, i32)> = moves[0..count]
-        .iter()
-        .map(|&m| (m, evaluate_move(m, phase)))
-        .collect();
-
-    evaluated_moves.sort_by(|a, b| b.1.cmp(&a.1));
-
-    for i in 0..count {
-        if tt_best_move.is_some() {
-            moves[i + 1] = evaluated_moves[i].0;
-        } else {
-            moves[i] = evaluated_moves[i].0;
-        }
+    // checks that don't just give material away
+    if gives_check && board.see_ge(m, 0) {
+        return true;
     }
-
-    if tt_best_move.is_some() {
-        moves[0] = tt_best_move.unwrap();
+    // taking back on the square of the last capture, on the principal variation only
+    if pv_node && last != Move::null() && !is_quiet(last) && !is_quiet(m) && last.to().0 == m.to().0 {
+        return true;
     }
+    // a passed pawn one step from promotion
+    let seventh = if m.is_white() { 6 } else { 1 };
+    matches!(m.role(), Role::Pawn)
+        && m.to().rank() == seventh
+        && pawns::is_passed(board, m.is_white(), m.to().0 as usize)
 }
 
-pub fn evaluate_move(m: Move, phase: i32) -> i32 {
-    let mut mg_score = 0;
-    let mut eg_score = 0;
+// margin on top of the captured piece for positional gains in delta pruning
+const DELTA_MARGIN: i32 = 200;
 
-    let file_from = m.from().0 % 8;
-    let rank_from = m.from().0 / 8;
+// resolves captures and promotions at the horizon, so positions are only
+// evaluated when they are quiet
+pub fn quiescence(
+    game: &mut Game,
+    ply: usize,
+    alpha: Score,
+    beta: Score,
+    context: &mut SearchContext,
+) -> Score {
+    context.pv.clear(ply);
+    context.count_node();
+    context.seldepth = context.seldepth.max(ply);
+    // captures are all it takes to get there
+    if game.insufficient_material() {
+        return Score::CP(-10);
+    }
+    let mut alpha = alpha;
 
-    let file_to = m.to().0 % 8;
-    let rank_to = m.to().0 / 8;
+    let in_check = game.in_check();
+    let stand_pat = evaluate(&game, &mut context.pawn_table);
+    if ply >= MAX_PLY - 1 {
+        return Score::CP(stand_pat);
+    }
 
-    let file_from = if m.is_white() { 7 - file_from } else { file_from };
-    let file_to = if m.is_white() { 7 - file_to } else { file_to };
+    // in check every evasion has to be tried, standing pat is not an option
+    let (mut moves, count) = if in_check {
+        game.get_legal_moves()
+    } else {
+        let stand_pat = Score::CP(stand_pat);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        game.get_legal_captures()
+    };
 
-    let rank_from = if m.is_white() { 7 - rank_from } else { rank_from };
-    let rank_to = if m.is_white() { 7 - rank_to } else { rank_to };
+    if in_check && count == 0 {
+        return Score::MateIn(-(ply as i32));
+    }
 
-    mg_score += match m.move_type() {
-        MoveType::Promotion => match m.promotion_role() {
-            crate::role::PromotionRole::Queen => mg_table(Role::Queen)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Rook => mg_table(Role::Rook)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Bishop => mg_table(Role::Bishop)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Knight => mg_table(Role::Knight)[rank_to as usize][file_to as usize],
-        },
-        MoveType::Quiet => 0,
-        MoveType::DoublePawnPush => 5,
-        MoveType::EnPassant => 5,
-        MoveType::KingsideCastle => 10,
-        MoveType::QueensideCastle => 10,
-    };
+    moves[0..count].sort_unstable_by_key(|&m| std::cmp::Reverse(mvv_lva(m)));
 
-    eg_score += match m.move_type() {
-        MoveType::Promotion => match m.promotion_role() {
-            crate::role::PromotionRole::Queen => eg_table(Role::Queen)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Rook => eg_table(Role::Rook)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Bishop => eg_table(Role::Bishop)[rank_to as usize][file_to as usize],
-            crate::role::PromotionRole::Knight => eg_table(Role::Knight)[rank_to as usize][file_to as usize],
-        },
-        MoveType::Quiet => 0,
-        MoveType::DoublePawnPush => 5,
-        MoveType::EnPassant => 5,
-        MoveType::KingsideCastle => 10,
-        MoveType::QueensideCastle => 10,
+    let mut best_value = if in_check {
+        Score::LOWEST
+    } else {
+        Score::CP(stand_pat)
     };
+    for &m in &moves[0..count] {
+        // even winning the piece for free can't raise alpha
+        if !in_check
+            && !matches!(m.move_type(), MoveType::Promotion)
+            && Score::CP(stand_pat + captured_value(m) + DELTA_MARGIN) <= alpha
+        {
+            continue;
+        }
+        // losing captures
+        if !in_check && !game.board.see_ge(m, 0) {
+            continue;
+        }
 
-    if m.is_capture() {
-        let mg_captured_value = mg_table(m.capture_role())[rank_to as usize][file_to as usize];
-        let eg_captured_value = eg_table(m.capture_role())[rank_to as usize][file_to as usize];
+        game.make_move(m);
+        let value = -quiescence(game, ply + 1, -beta, -alpha, context);
+        game.unmake_move();
 
-        // winning capture
-        if m.capture_role() as u8 >= m.role() as u8 {
-            mg_score += 10;
-            eg_score += 10;
-        } else {
-            mg_score -= 10;
-            eg_score -= 10;
+        if value > best_value {
+            best_value = value;
         }
-
-        mg_score += mg_captured_value + 5;
-        eg_score += eg_captured_value + 5;
+        if best_value >= beta {
+            return best_value;
+        }
+        alpha = alpha.max(best_value);
     }
 
-    mg_score += mg_table(m.role())[rank_to as usize][file_to as usize];
-    eg_score += eg_table(m.role())[rank_to as usize][file_to as usize];
-
-    mg_score -= mg_table(m.role())[rank_from as usize][file_from as usize];
-    eg_score -= eg_table(m.role())[rank_from as usize][file_from as usize];
-
-    ((mg_score * (256 - phase)) + (eg_score * phase)) / 256
+    best_value
 }
 
-fn mg_table(role: Role) -> &'static [[i32; 8]; 8] {
-    match role {
-        Role::Pawn => &MG_PAWN_TABLE,
-        Role::Bishop => &MG_BISHOP_TABLE,
-        Role::Knight => &MG_KNIGHT_TABLE,
-        Role::Rook => &MG_ROOK_TABLE,
-        Role::King => &MG_KING_TABLE,
-        Role::Queen => &MG_QUEEN_TABLE,
+pub fn evaluate(game: &Game, pawn_table: &mut PawnTable) -> i32 {
+    #[cfg(feature = "nnue")]
+    if let Some(network) = crate::nnue::network() {
+        return network.evaluate(&game.nnue, &game.board, game.is_white);
     }
+    eval::evaluate(&game.board, game.is_white, pawn_table)
 }
 
-fn eg_table(role: Role) -> &'static [[i32; 8]; 8] {
-    match role {
-        Role::Pawn => &EG_PAWN_TABLE,
-        Role::Bishop => &EG_BISHOP_TABLE,
-        Role::Knight => &EG_KNIGHT_TABLE,
-        Role::Rook => &EG_ROOK_TABLE,
-        Role::King => &EG_KING_TABLE,
-        Role::Queen => &EG_QUEEN_TABLE,
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn mg_piece_val(role: Role) -> i32 {
-    match role {
-        Role::Pawn => 82,
-        Role::Knight => 337,
-        Role::Bishop => 365,
-        Role::Rook => 477,
-        Role::Queen => 1025,
-        Role::King => 0,
+    #[test]
+    fn quiescence_sees_the_recapture() {
+        // Qxd5 wins a pawn at depth 1 but loses the queen to exd5
+        let game = Game::from_fen("7k/8/4p3/3p4/8/8/8/K2Q4 w - - 0 1").expect("invalid fen");
+        let tt = TranspositionTable::new(1);
+        let limits = SearchLimits {
+            depth: Some(1),
+            ..SearchLimits::default()
+        };
+        let result = search(game, &limits, &tt, &Options::new(), &Signals::default());
+        assert_ne!(result.best_move.expect("no best move").to_algebraic(), "d1d5");
     }
-}
 
-fn eg_piece_val(role: Role) -> i32 {
-    match role {
-        Role::Pawn => 94,
-        Role::Knight => 281,
-        Role::Bishop => 297,
-        Role::Rook => 512,
-        Role::Queen => 936,
-        Role::King => 0,
+    #[test]
+    fn pruning_keeps_the_mate() {
+        // Qg7 mates, none of the pruning may hide it
+        let game = Game::from_fen("7k/8/5KQ1/8/8/8/8/8 w - - 0 1").expect("invalid fen");
+        let limits = SearchLimits {
+            depth: Some(6),
+            ..SearchLimits::default()
+        };
+        let mut options = Options::new();
+        for enabled in ["true", "false"] {
+            for name in ["NullMove", "LMR", "ReverseFutility", "Futility", "LateMovePruning"] {
+                options.set(name, Some(enabled)).unwrap();
+            }
+            let tt = TranspositionTable::new(1);
+            let result = search(game.clone(), &limits, &tt, &options, &Signals::default());
+            let mut game = game.clone();
+            let best_move = result.best_move.expect("no best move");
+            game.make_move(best_move);
+            assert!(game.in_check() && game.get_legal_moves().1 == 0, "{}", best_move);
+        }
     }
-}
 
-fn game_phase_val(role: Role) -> i32 {
-    match role {
-        Role::Pawn => 0,
-        Role::Knight => 1,
-        Role::Bishop => 1,
-        Role::Rook => 2,
-        Role::Queen => 4,
-        Role::King => 0,
+    #[test]
+    fn aspiration_recovers_from_a_wrong_guess() {
+        // a pawn up for white, the window starts far off on either side
+        let mut game = Game::from_fen("7k/5ppp/8/8/8/8/4PPPP/7K w - - 0 1").expect("invalid fen");
+        let (moves, count) = game.get_legal_moves();
+        let signals = Signals::default();
+        let shared = Shared::new(1);
+        let options = Options::new();
+
+        let mut scores = Vec::new();
+        for guess in [None, Some(-600), Some(600)] {
+            let tt = TranspositionTable::new(1);
+            let mut context = SearchContext::new(&tt, &signals, &shared, 0, &options);
+            let mut root_moves: Vec<RootMove> = moves[0..count].iter().map(|&m| RootMove::new(m)).collect();
+            root_moves[0].previous_score = guess.map_or(Score::LOWEST, Score::CP);
+            aspiration(&mut game, &mut root_moves, 5, &mut context);
+            scores.push(root_moves[0].score);
+        }
+        // pruning makes the exact score depend on the window, but not by much
+        for score in scores {
+            assert!(score > Score::CP(50) && score < Score::CP(250), "{:?}", score);
+        }
     }
-}
-
-fn game_phase(board: Board) -> i32 {
-    let total_phase = game_phase_val(Role::Pawn) * 16 + game_phase_val(Role::Knight) * 4 + game_phase_val(Role::Bishop) * 4 + game_phase_val(Role::Rook) * 4 + game_phase_val(Role::Queen) * 2;
-    let mut phase = total_phase;
 
-    phase -= (board.by_role.pawns & board.by_color.white).0.count_ones() as i32 * game_phase_val(Role::Pawn);
-    phase -= (board.by_role.knights & board.by_color.white).0.count_ones() as i32 * game_phase_val(Role::Knight);
-
-    phase -= (board.by_role.rooks & board.by_color.black).0.count_ones() as i32 * game_phase_val(Role::Rook);
-    phase -= (board.by_role.queens & board.by_color.black).0.count_ones() as i32 * game_phase_val(Role::Queen);
-
-
-    (phase * 256 + (total_phase / 2)) / total_phase
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn helpers_skip_depths() {
+        for depth in 1..MAX_DEPTH {
+            assert!(!skips_depth(0, depth));
+        }
+        // the first two helpers alternate, every helper searches half of the depths
+        assert_ne!(skips_depth(1, 5), skips_depth(2, 5));
+        for thread in 1..=20 {
+            let skipped = (1..=24).filter(|&depth| skips_depth(thread, depth)).count();
+            assert_eq!(skipped, 12, "thread {}", thread);
+        }
+    }
 
     #[test]
     fn position_evaluation_equal() {
         let b = Game::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").expect("invalid fen");
-        assert_eq!(evaluate(&b), 0);
+        assert_eq!(evaluate(&b, &mut PawnTable::new()), 0);
     }
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use std::time::{Duration, Instant};
+
+use crate::uci::{SearchLimits, TimeControl};
+
+// without movestogo the clock is shared among this many moves
+const SUDDEN_DEATH_MOVES: u64 = 30;
+const MAX_MOVES_TO_GO: u64 = 50;
+// the hard limit is this many times the optimum, and never more than this percentage of the clock
+const MAXIMUM_RATIO: u64 = 5;
+const MAXIMUM_SHARE: u64 = 75;
+// milliseconds given even when the overhead eats up the whole allocation
+const MINIMUM_TIME: u64 = 1;
+// part of the optimum used when the best move has stayed the same for that many iterations
+const STABILITY_SCALE: [f64; 5] = [2.0, 1.4, 1.1, 0.9, 0.8];
+// centipawns the score has to fall from one iteration to the next to double the optimum
+const SCORE_DROP_DOUBLES: i32 = 100;
+// a best move that took this share of the iteration's nodes leaves the optimum as it is,
+// a larger share means the other moves were refuted quickly
+const BEST_MOVE_SHARE: f64 = 0.5;
+
+// how long the search of one move may take. Between iterations the search stops
+// once the optimum time is used up, scaled by how settled the search is, and
+// inside it once the maximum time is.
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    optimum: Option<Duration>,
+    maximum: Option<Duration>,
+    // movetime is used as given
+    scalable: bool,
+    scale: f64,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, is_white: bool, move_overhead: u64) -> TimeManager {
+        let mut manager = TimeManager::infinite();
+        match limits.time_control {
+            _ if limits.infinite => {}
+            TimeControl::Infinite => {}
+            TimeControl::Movetime(time) => {
+                let time = Duration::from_millis(time.saturating_sub(move_overhead).max(MINIMUM_TIME));
+                manager.optimum = Some(time);
+                manager.maximum = Some(time);
+                manager.scalable = false;
+            }
+            TimeControl::RemainingTime {
+                white,
+                black,
+                winc,
+                binc,
+                movestogo,
+            } => {
+                let (time, increment) = if is_white { (white, winc) } else { (black, binc) };
+                let (optimum, maximum) = allocate(time, increment, movestogo, move_overhead);
+                manager.optimum = Some(Duration::from_millis(optimum.max(MINIMUM_TIME)));
+                manager.maximum = Some(Duration::from_millis(maximum.max(MINIMUM_TIME)));
+            }
+        }
+        manager
+    }
+
+    pub fn infinite() -> TimeManager {
+        TimeManager {
+            start: Instant::now(),
+            optimum: None,
+            maximum: None,
+            scalable: true,
+            scale: 1.0,
+        }
+    }
+
+    // the time spent pondering doesn't count, the clock starts on ponderhit
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.maximum
+            .is_some_and(|maximum| self.start.elapsed() >= maximum)
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        self.optimum
+            .is_some_and(|optimum| self.start.elapsed() >= optimum.mul_f64(self.scale))
+    }
+
+    // `stability` is the number of iterations the best move hasn't changed,
+    // `score_drop` how much worse the last iteration's score is than the one before
+    // and `best_move_share` the part of the last iteration's nodes spent on the best move
+    pub fn adjust(&mut self, stability: usize, score_drop: i32, best_move_share: f64) {
+        if !self.scalable {
+            return;
+        }
+        let drop = score_drop.clamp(0, SCORE_DROP_DOUBLES) as f64 / SCORE_DROP_DOUBLES as f64;
+        let effort = 1.0 + BEST_MOVE_SHARE - best_move_share.clamp(0.0, 1.0);
+        self.scale = STABILITY_SCALE[stability.min(STABILITY_SCALE.len() - 1)] * (1.0 + drop) * effort;
+    }
+}
+
+// optimum and maximum milliseconds for the move. The increment of every move to come
+// is as good as time on the clock, so most of it can be spent right away.
+fn allocate(time: u64, increment: u64, movestogo: Option<u64>, move_overhead: u64) -> (u64, u64) {
+    let available = time.saturating_sub(move_overhead);
+    let moves = movestogo.map_or(SUDDEN_DEATH_MOVES, |moves| moves.clamp(1, MAX_MOVES_TO_GO));
+    let limit = available * MAXIMUM_SHARE / 100;
+
+    let optimum = (available / moves + increment * 3 / 4).min(limit);
+    let maximum = (optimum * MAXIMUM_RATIO).min(limit);
+    (optimum, maximum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sudden_death_spreads_the_clock() {
+        let (optimum, maximum) = allocate(60_000, 0, None, 10);
+        assert_eq!(optimum, 59_990 / SUDDEN_DEATH_MOVES);
+        assert_eq!(maximum, optimum * MAXIMUM_RATIO);
+    }
+
+    #[test]
+    fn increment_is_spent() {
+        let (without, _) = allocate(10_000, 0, None, 0);
+        let (with, _) = allocate(10_000, 1_000, None, 0);
+        assert_eq!(with, without + 750);
+    }
+
+    #[test]
+    fn never_flags() {
+        // a last move before the time control or almost no time left
+        for (time, increment, movestogo) in [(1_000, 0, Some(1)), (50, 0, None), (100, 2_000, None), (5, 0, Some(3))] {
+            let (optimum, maximum) = allocate(time, increment, movestogo, 10);
+            assert!(optimum <= maximum);
+            assert!(maximum <= time.saturating_sub(10) * MAXIMUM_SHARE / 100, "{} {}", time, increment);
+        }
+    }
+
+    #[test]
+    fn overhead_leaves_some_time() {
+        let limits = SearchLimits {
+            time_control: TimeControl::Movetime(5),
+            ..SearchLimits::default()
+        };
+        let manager = TimeManager::new(&limits, true, 10);
+        assert_eq!(manager.maximum, Some(Duration::from_millis(MINIMUM_TIME)));
+    }
+
+    #[test]
+    fn unstable_searches_get_more_time() {
+        let limits = SearchLimits {
+            time_control: TimeControl::RemainingTime {
+                white: 60_000,
+                black: 60_000,
+                winc: 0,
+                binc: 0,
+                movestogo: None,
+            },
+            ..SearchLimits::default()
+        };
+        let mut manager = TimeManager::new(&limits, true, 0);
+        manager.adjust(0, 0, 0.5);
+        let changing = manager.scale;
+        manager.adjust(4, 0, 0.5);
+        let settled = manager.scale;
+        manager.adjust(4, 50, 0.5);
+        assert!(changing > settled);
+        assert!(manager.scale > settled);
+        manager.adjust(4, 0, 0.95);
+        assert!(manager.scale < settled);
+    }
+}
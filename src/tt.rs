@@ -0,0 +1,297 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::r#move::Move;
+use crate::score::Score;
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+const BUCKET_SIZE: usize = 4;
+const GENERATION_MASK: u8 = 0b11_1111;
+
+// scores are packed into an i16, mates are stored as MATE_VALUE - distance
+const MATE_VALUE: i32 = 32_000;
+const MATE_BOUND: i32 = 31_000;
+const MAX_CP: i32 = 30_000;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NodeType {
+    Cut = 0,
+    All = 1,
+    PV = 2,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct TTEntry {
+    pub score: Score,
+    pub depth: u32,
+    pub node_type: NodeType,
+    pub best_move: Move,
+}
+
+// 32: 0-31  move
+// 16: 32-47 score
+// 8:  48-55 depth
+// 2:  56-57 node type Cut = 0, All = 1, PV = 2
+// 6:  58-63 generation
+impl TTEntry {
+    fn pack(&self, generation: u8) -> u64 {
+        let score = encode_score(self.score) as u16 as u64;
+        let depth = self.depth.min(u8::MAX as u32) as u64;
+
+        self.best_move.0 as u64
+            | score << 32
+            | depth << 48
+            | (self.node_type as u64) << 56
+            | ((generation & GENERATION_MASK) as u64) << 58
+    }
+
+    fn unpack(data: u64) -> TTEntry {
+        let node_type = match (data >> 56) & 0b11 {
+            0 => NodeType::Cut,
+            1 => NodeType::All,
+            _ => NodeType::PV,
+        };
+
+        TTEntry {
+            score: decode_score((data >> 32) as u16 as i16),
+            depth: ((data >> 48) & 0xFF) as u32,
+            node_type,
+            best_move: Move(data as u32),
+        }
+    }
+}
+
+// the search counts mates in plies from the root, the table in plies from the entry's
+// position, so they stay right when the position comes up again at another ply
+pub fn score_to_tt(score: Score, ply: usize) -> Score {
+    match score {
+        Score::MateIn(mate) if mate > 0 => Score::MateIn(mate - ply as i32),
+        Score::MateIn(mate) => Score::MateIn(mate + ply as i32),
+        score => score,
+    }
+}
+
+pub fn score_from_tt(score: Score, ply: usize) -> Score {
+    match score {
+        Score::MateIn(mate) if mate > 0 => Score::MateIn(mate + ply as i32),
+        Score::MateIn(mate) => Score::MateIn(mate - ply as i32),
+        score => score,
+    }
+}
+
+fn encode_score(score: Score) -> i16 {
+    let value = match score {
+        Score::MateIn(mate) if mate < 0 => -MATE_VALUE - mate,
+        Score::MateIn(mate) => MATE_VALUE - mate,
+        Score::CP(cp) => cp.clamp(-MAX_CP, MAX_CP),
+    };
+    value as i16
+}
+
+fn decode_score(value: i16) -> Score {
+    let value = value as i32;
+    if value >= MATE_BOUND {
+        Score::MateIn(MATE_VALUE - value)
+    } else if value <= -MATE_BOUND {
+        Score::MateIn(-(MATE_VALUE + value))
+    } else {
+        Score::CP(value)
+    }
+}
+
+fn generation_of(data: u64) -> u8 {
+    (data >> 58) as u8 & GENERATION_MASK
+}
+
+fn depth_of(data: u64) -> u32 {
+    ((data >> 48) & 0xFF) as u32
+}
+
+// The key is stored xor'ed with the data, so an entry torn by a concurrent
+// write from another search thread fails the key check instead of being used.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    slots: [Slot; BUCKET_SIZE],
+}
+
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+
+        Self {
+            buckets: (0..count).map(|_| Bucket::default()).collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for bucket in &self.buckets {
+            for slot in &bucket.slots {
+                slot.key.store(0, Ordering::Relaxed);
+                slot.data.store(0, Ordering::Relaxed);
+            }
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // has to be called once before every search, so entries from older
+    // searches are preferred when replacing
+    pub fn new_search(&self) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.generation
+            .store(generation.wrapping_add(1) & GENERATION_MASK, Ordering::Relaxed);
+    }
+
+    fn bucket(&self, key: u64) -> &Bucket {
+        let index = ((key as u128 * self.buckets.len() as u128) >> 64) as usize;
+        &self.buckets[index]
+    }
+
+    pub fn get(&self, key: u64) -> Option<TTEntry> {
+        for slot in &self.bucket(key).slots {
+            let data = slot.data.load(Ordering::Relaxed);
+            if data != 0 && slot.key.load(Ordering::Relaxed) ^ data == key {
+                return Some(TTEntry::unpack(data));
+            }
+        }
+        None
+    }
+
+    pub fn insert(&self, key: u64, value: TTEntry) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let bucket = self.bucket(key);
+
+        let mut replace = &bucket.slots[0];
+        let mut replace_value = i32::MAX;
+        for slot in &bucket.slots {
+            let data = slot.data.load(Ordering::Relaxed);
+
+            if data == 0 {
+                if replace_value > i32::MIN {
+                    replace = slot;
+                    replace_value = i32::MIN;
+                }
+                continue;
+            }
+
+            if slot.key.load(Ordering::Relaxed) ^ data == key {
+                // keep deeper results of the current search for the same position
+                if value.node_type != NodeType::PV
+                    && generation_of(data) == generation
+                    && value.depth + 2 < depth_of(data)
+                {
+                    return;
+                }
+                replace = slot;
+                break;
+            }
+
+            // prefer replacing shallow entries and entries from older searches
+            let age = generation.wrapping_sub(generation_of(data)) & GENERATION_MASK;
+            let slot_value = depth_of(data) as i32 - 8 * age as i32;
+            if slot_value < replace_value {
+                replace = slot;
+                replace_value = slot_value;
+            }
+        }
+
+        let data = value.pack(generation);
+        replace.key.store(key ^ data, Ordering::Relaxed);
+        replace.data.store(data, Ordering::Relaxed);
+    }
+
+    // permill of sampled entries that were written in the current search
+    pub fn hashfull(&self) -> u64 {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sampled = self.buckets.len().min(1000 / BUCKET_SIZE);
+
+        let used: usize = self.buckets[..sampled]
+            .iter()
+            .map(|bucket| {
+                bucket
+                    .slots
+                    .iter()
+                    .filter(|slot| {
+                        let data = slot.data.load(Ordering::Relaxed);
+                        data != 0 && generation_of(data) == generation
+                    })
+                    .count()
+            })
+            .sum();
+
+        (used * 1000 / (sampled * BUCKET_SIZE)) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: Score, depth: u32) -> TTEntry {
+        TTEntry {
+            score,
+            depth,
+            node_type: NodeType::Cut,
+            best_move: Move(0b1010_1010),
+        }
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let tt = TranspositionTable::new(1);
+        tt.insert(42, entry(Score::CP(-123), 7));
+
+        let found = tt.get(42).expect("entry not found");
+        assert_eq!(found.score, Score::CP(-123));
+        assert_eq!(found.depth, 7);
+        assert_eq!(found.node_type, NodeType::Cut);
+        assert_eq!(found.best_move, Move(0b1010_1010));
+        assert!(tt.get(43).is_none());
+    }
+
+    #[test]
+    fn mate_scores_survive_packing() {
+        let tt = TranspositionTable::new(1);
+        for mate in [1, 5, 40, -1, -5, -40] {
+            tt.insert(7, entry(Score::MateIn(mate), 3));
+            assert_eq!(tt.get(7).unwrap().score, Score::MateIn(mate));
+        }
+    }
+
+    #[test]
+    fn mates_are_stored_from_the_node() {
+        // a mate on ply 7 of the search is 4 plies away from a node on ply 3
+        assert_eq!(score_to_tt(Score::MateIn(7), 3), Score::MateIn(4));
+        assert_eq!(score_to_tt(Score::MateIn(-6), 3), Score::MateIn(-3));
+        // and 6 plies from the root when the node comes up again on ply 2
+        assert_eq!(score_from_tt(Score::MateIn(4), 2), Score::MateIn(6));
+        assert_eq!(score_from_tt(Score::MateIn(-3), 2), Score::MateIn(-5));
+        assert_eq!(score_from_tt(score_to_tt(Score::CP(55), 9), 1), Score::CP(55));
+    }
+
+    #[test]
+    fn size_is_fixed() {
+        let tt = TranspositionTable::new(1);
+        let buckets = tt.buckets.len();
+        for key in 0..100_000u64 {
+            tt.insert(key.wrapping_mul(0x9E37_79B9_7F4A_7C15), entry(Score::CP(1), 1));
+        }
+        assert_eq!(tt.buckets.len(), buckets);
+        assert!(tt.hashfull() > 900);
+
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+    }
+}
@@ -0,0 +1,387 @@
+// Texel tuning: the weights of the evaluation are fitted to the results of games the
+// positions were taken from. The evaluation is linear in its weights, so every position
+// is reduced to how often each weight counts for white minus black, and the mean
+// squared error of the win probability is minimised with gradient descent.
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+use crate::board::Board;
+use crate::eval::{self, EvalParams, MAX_PHASE};
+use crate::pawns;
+use crate::role::Role;
+use crate::values::KING_ATTACK_SCALE;
+
+// offsets of the groups in EvalParams::weights_mut
+const PIECE_VALUES: usize = 0;
+const TABLES: usize = PIECE_VALUES + 6;
+const PASSED_PAWN: usize = TABLES + 6 * 64;
+const CONNECTED_PAWN: usize = PASSED_PAWN + 8;
+const DOUBLED_PAWN: usize = CONNECTED_PAWN + 8;
+const ISOLATED_PAWN: usize = DOUBLED_PAWN + 1;
+const BACKWARD_PAWN: usize = ISOLATED_PAWN + 1;
+const MOBILITY: usize = BACKWARD_PAWN + 1;
+const PAWN_SHIELD: usize = MOBILITY + 6;
+const KING_SEMI_OPEN_FILE: usize = PAWN_SHIELD + 2;
+const KING_OPEN_FILE: usize = KING_SEMI_OPEN_FILE + 1;
+const KING_ATTACK_WEIGHT: usize = KING_OPEN_FILE + 1;
+pub const WEIGHTS: usize = KING_ATTACK_WEIGHT + 6;
+
+const ROLES: [Role; 6] = [
+    Role::Pawn,
+    Role::Bishop,
+    Role::Knight,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
+
+pub struct Entry {
+    // weight index and how often it counts for white minus black
+    coefficients: Vec<(u16, f64)>,
+    // share of the middlegame score in the tapered score
+    phase: f64,
+    // part of the endgame score that counts when white or black is ahead in it
+    scale: [f64; 2],
+    // 1 for a white win, 0.5 for a draw and 0 for a black win
+    result: f64,
+}
+
+pub fn coefficients(board: &Board) -> Vec<(u16, f64)> {
+    let mut dense = [0.0; WEIGHTS];
+
+    for square in 0..64 {
+        let Some(piece) = board.piece_at(square) else {
+            continue;
+        };
+        let sign = if piece.is_white { 1.0 } else { -1.0 };
+        let (row, file) = eval::table_index(piece.is_white, square as usize);
+        dense[PIECE_VALUES + piece.role as usize] += sign;
+        dense[TABLES + piece.role as usize * 64 + row * 8 + file] += sign;
+    }
+
+    let activity = [eval::activity(board, true), eval::activity(board, false)];
+    for (side, is_white) in [(0, true), (1, false)] {
+        let sign = if is_white { 1.0 } else { -1.0 };
+
+        let pawns = pawns::features(board, is_white);
+        for rank in 0..8 {
+            dense[PASSED_PAWN + rank] += sign * pawns.passed[rank] as f64;
+            dense[CONNECTED_PAWN + rank] += sign * pawns.connected[rank] as f64;
+        }
+        dense[DOUBLED_PAWN] += sign * pawns.doubled as f64;
+        dense[ISOLATED_PAWN] += sign * pawns.isolated as f64;
+        dense[BACKWARD_PAWN] += sign * pawns.backward as f64;
+
+        for role in ROLES {
+            dense[MOBILITY + role as usize] += sign * activity[side].mobility[role as usize] as f64;
+        }
+
+        let shelter = eval::king_shelter(board, is_white);
+        dense[PAWN_SHIELD] += sign * shelter.shield[0] as f64;
+        dense[PAWN_SHIELD + 1] += sign * shelter.shield[1] as f64;
+        dense[KING_SEMI_OPEN_FILE] += sign * shelter.semi_open_files as f64;
+        dense[KING_OPEN_FILE] += sign * shelter.open_files as f64;
+
+        // the scale depends on the number of attackers only, not on the weights
+        let enemy = &activity[1 - side];
+        let scale = KING_ATTACK_SCALE[enemy.attackers.min(7)] as f64 / 100.0;
+        for role in ROLES {
+            dense[KING_ATTACK_WEIGHT + role as usize] -=
+                sign * enemy.king_attacks[role as usize] as f64 * scale;
+        }
+    }
+
+    dense
+        .iter()
+        .enumerate()
+        .filter(|(_, &coefficient)| coefficient != 0.0)
+        .map(|(i, &coefficient)| (i as u16, coefficient))
+        .collect()
+}
+
+// a fen followed by the result as 1-0, 0-1, 1/2-1/2 or a number, optionally quoted,
+// in brackets or after a semicolon
+pub fn parse_line(line: &str) -> Option<(Board, f64)> {
+    let line = line.trim().trim_end_matches(';');
+    let split = line.rfind(|c: char| c.is_whitespace() || c == ';')?;
+    let (fen, result) = line.split_at(split);
+    let result = result.trim_matches(|c: char| c.is_whitespace() || "[]\";".contains(c));
+    let result = match result {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        "1/2-1/2" => 0.5,
+        number => number.parse::<f64>().ok().filter(|r| (0.0..=1.0).contains(r))?,
+    };
+
+    let fen = fen.trim().trim_end_matches(';').trim();
+    if fen.split('/').count() != 8 {
+        return None;
+    }
+    Some((Board::from_fen(fen), result))
+}
+
+pub fn load(path: &str) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let Some((board, result)) = parse_line(&line?) else {
+            continue;
+        };
+        entries.push(Entry {
+            coefficients: coefficients(&board),
+            phase: board.phase.min(MAX_PHASE) as f64 / MAX_PHASE as f64,
+            scale: scales(&board),
+            result,
+        });
+    }
+    Ok(entries)
+}
+
+fn scales(board: &Board) -> [f64; 2] {
+    [true, false].map(|is_white| eval::scale_factor(board, is_white) as f64 / eval::SCALE_NORMAL as f64)
+}
+
+// middlegame and endgame score from white's point of view
+fn sums(entry: &Entry, weights: &[(f64, f64)]) -> (f64, f64) {
+    entry
+        .coefficients
+        .iter()
+        .fold((0.0, 0.0), |(mg, eg), &(i, coefficient)| {
+            let (weight_mg, weight_eg) = weights[i as usize];
+            (mg + coefficient * weight_mg, eg + coefficient * weight_eg)
+        })
+}
+
+// weight of the endgame score in the tapered score
+fn endgame_weight(entry: &Entry, eg: f64) -> f64 {
+    let scale = if eg > 0.0 { entry.scale[0] } else { entry.scale[1] };
+    (1.0 - entry.phase) * scale
+}
+
+// tapered score from white's point of view
+fn linear_eval(entry: &Entry, weights: &[(f64, f64)]) -> f64 {
+    let (mg, eg) = sums(entry, weights);
+    mg * entry.phase + eg * endgame_weight(entry, eg)
+}
+
+// expected result for white of a position with this score
+fn sigmoid(k: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+pub fn error(entries: &[Entry], weights: &[(f64, f64)], k: f64) -> f64 {
+    let sum: f64 = entries
+        .iter()
+        .map(|entry| (entry.result - sigmoid(k, linear_eval(entry, weights))).powi(2))
+        .sum();
+    sum / entries.len().max(1) as f64
+}
+
+// the scaling between scores and results that fits the current weights best
+pub fn find_k(entries: &[Entry], weights: &[(f64, f64)]) -> f64 {
+    let mut best = 1.0;
+    let mut step = 0.5;
+    for _ in 0..10 {
+        let candidates = [best - step, best, best + step];
+        best = candidates
+            .into_iter()
+            .filter(|&k| k > 0.0)
+            .min_by(|&a, &b| error(entries, weights, a).total_cmp(&error(entries, weights, b)))
+            .unwrap();
+        step /= 2.0;
+    }
+    best
+}
+
+fn to_weights(params: &EvalParams) -> Vec<(f64, f64)> {
+    params
+        .clone()
+        .weights_mut()
+        .into_iter()
+        .map(|&mut (mg, eg)| (mg as f64, eg as f64))
+        .collect()
+}
+
+// Adam on the mean squared error, `progress` is called with the epoch and the error
+pub fn tune(
+    entries: &[Entry],
+    params: &EvalParams,
+    epochs: usize,
+    learning_rate: f64,
+    mut progress: impl FnMut(usize, f64),
+) -> EvalParams {
+    const BETA1: f64 = 0.9;
+    const BETA2: f64 = 0.999;
+    const EPSILON: f64 = 1e-8;
+
+    let mut weights = to_weights(params);
+    let k = find_k(entries, &weights);
+    let mut momentum = vec![(0.0, 0.0); WEIGHTS];
+    let mut velocity = vec![(0.0, 0.0); WEIGHTS];
+
+    for epoch in 1..=epochs {
+        let mut gradient = vec![(0.0, 0.0); WEIGHTS];
+        for entry in entries {
+            let (mg, eg) = sums(entry, &weights);
+            let eg_weight = endgame_weight(entry, eg);
+            let expected = sigmoid(k, mg * entry.phase + eg * eg_weight);
+            let slope = (expected - entry.result)
+                * expected
+                * (1.0 - expected)
+                * k
+                * std::f64::consts::LN_10
+                / 400.0;
+            for &(i, coefficient) in &entry.coefficients {
+                gradient[i as usize].0 += slope * coefficient * entry.phase;
+                gradient[i as usize].1 += slope * coefficient * eg_weight;
+            }
+        }
+
+        let scale = 2.0 / entries.len().max(1) as f64;
+        let correction = (1.0 - BETA1.powi(epoch as i32), 1.0 - BETA2.powi(epoch as i32));
+        let adam = |weight: &mut f64, gradient: f64, momentum: &mut f64, velocity: &mut f64| {
+            let gradient = gradient * scale;
+            *momentum = BETA1 * *momentum + (1.0 - BETA1) * gradient;
+            *velocity = BETA2 * *velocity + (1.0 - BETA2) * gradient * gradient;
+            let step = (*momentum / correction.0) / ((*velocity / correction.1).sqrt() + EPSILON);
+            *weight -= learning_rate * step;
+        };
+        for i in 0..WEIGHTS {
+            let (weight_mg, weight_eg) = &mut weights[i];
+            let (momentum_mg, momentum_eg) = &mut momentum[i];
+            let (velocity_mg, velocity_eg) = &mut velocity[i];
+            adam(weight_mg, gradient[i].0, momentum_mg, velocity_mg);
+            adam(weight_eg, gradient[i].1, momentum_eg, velocity_eg);
+        }
+
+        progress(epoch, error(entries, &weights, k));
+    }
+
+    let mut tuned = params.clone();
+    for (weight, (mg, eg)) in tuned.weights_mut().into_iter().zip(weights) {
+        *weight = (mg.round() as i32, eg.round() as i32);
+    }
+    tuned
+}
+
+fn pairs(pairs: &[(i32, i32)]) -> String {
+    let pairs: Vec<String> = pairs.iter().map(|(mg, eg)| format!("({}, {})", mg, eg)).collect();
+    format!("[{}]", pairs.join(", "))
+}
+
+// writes the weights as the source of src/eval/params.rs
+pub fn write_params(params: &EvalParams, path: &str) -> io::Result<()> {
+    let mut output = File::create(path)?;
+    writeln!(
+        output,
+        "// generated by the tuner (src/tune.rs), every weight is (middlegame, endgame)"
+    )?;
+    writeln!(output, "use crate::eval::EvalParams;\n")?;
+    writeln!(output, "pub static PARAMS: EvalParams = EvalParams {{")?;
+    writeln!(output, "    piece_values: {},", pairs(&params.piece_values))?;
+    writeln!(output, "    tables: [")?;
+    for (role, table) in ROLES.iter().zip(&params.tables) {
+        writeln!(output, "        // {:?}", role)?;
+        writeln!(output, "        [")?;
+        for row in table {
+            writeln!(output, "            {},", pairs(row))?;
+        }
+        writeln!(output, "        ],")?;
+    }
+    writeln!(output, "    ],")?;
+    writeln!(output, "    passed_pawn: {},", pairs(&params.passed_pawn))?;
+    writeln!(output, "    connected_pawn: {},", pairs(&params.connected_pawn))?;
+    writeln!(output, "    doubled_pawn: {:?},", params.doubled_pawn)?;
+    writeln!(output, "    isolated_pawn: {:?},", params.isolated_pawn)?;
+    writeln!(output, "    backward_pawn: {:?},", params.backward_pawn)?;
+    writeln!(output, "    mobility: {},", pairs(&params.mobility))?;
+    writeln!(output, "    pawn_shield: {},", pairs(&params.pawn_shield))?;
+    writeln!(output, "    king_semi_open_file: {:?},", params.king_semi_open_file)?;
+    writeln!(output, "    king_open_file: {:?},", params.king_open_file)?;
+    writeln!(output, "    king_attack_weight: {},", pairs(&params.king_attack_weight))?;
+    writeln!(output, "}};")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::PARAMS;
+    use crate::pawns::PawnTable;
+
+    const POSITIONS: [&str; 4] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bq1rk1/pppp1ppp/2n2n2/4p3/4P3/2NK1N2/PPPP1PPP/R1BQ1R2 b - - 0 1",
+        "6k1/5ppp/8/7q/8/8/5PPP/3r2K1 w - - 0 1",
+        "8/5pk1/6p1/3P4/1p6/8/5PPP/6K1 w - - 0 1",
+    ];
+
+    fn entry(fen: &str, result: f64) -> Entry {
+        let board = Board::from_fen(fen);
+        Entry {
+            coefficients: coefficients(&board),
+            phase: board.phase.min(MAX_PHASE) as f64 / MAX_PHASE as f64,
+            scale: scales(&board),
+            result,
+        }
+    }
+
+    #[test]
+    fn offsets_match_the_params() {
+        let mut params = PARAMS.clone();
+        let weights = params.weights_mut();
+        assert_eq!(weights.len(), WEIGHTS);
+        assert_eq!(*weights[DOUBLED_PAWN], PARAMS.doubled_pawn);
+        assert_eq!(*weights[KING_OPEN_FILE], PARAMS.king_open_file);
+        assert_eq!(*weights[KING_ATTACK_WEIGHT + 4], PARAMS.king_attack_weight[4]);
+    }
+
+    #[test]
+    fn linear_eval_matches_evaluate() {
+        let weights = to_weights(&PARAMS);
+        for fen in POSITIONS {
+            let linear = linear_eval(&entry(fen, 0.5), &weights);
+            let score = eval::evaluate(&Board::from_fen(fen), true, &mut PawnTable::new());
+            // only the rounding of the king attacks and the taper differ
+            assert!((linear - score as f64).abs() <= 2.0, "{}: {} != {}", fen, linear, score);
+        }
+    }
+
+    #[test]
+    fn parses_results() {
+        let fen = "8/5pk1/6p1/3P4/1p6/8/5PPP/6K1 w - - 0 1";
+        for (line, expected) in [
+            (format!("{} 1-0", fen), 1.0),
+            (format!("{} \"0-1\";", fen), 0.0),
+            (format!("{} [0.5]", fen), 0.5),
+            (format!("{}; 1/2-1/2", fen), 0.5),
+        ] {
+            let (_, result) = parse_line(&line).expect("unparsed line");
+            assert_eq!(result, expected, "{}", line);
+        }
+        assert!(parse_line(&format!("{} 2-0", fen)).is_none());
+    }
+
+    #[test]
+    fn written_params_match_the_source() {
+        let path = std::env::temp_dir().join("gegene_params.rs");
+        write_params(&PARAMS, path.to_str().unwrap()).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, include_str!("eval/params.rs"));
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        let entries: Vec<Entry> = POSITIONS
+            .iter()
+            .zip([0.5, 0.0, 0.0, 1.0])
+            .map(|(fen, result)| entry(fen, result))
+            .collect();
+        let weights = to_weights(&PARAMS);
+        let k = find_k(&entries, &weights);
+        let before = error(&entries, &weights, k);
+
+        let mut last = before;
+        let tuned = tune(&entries, &PARAMS, 20, 2.0, |_, error| last = error);
+        assert!(last < before, "{} >= {}", last, before);
+        assert_ne!(tuned, PARAMS);
+    }
+}
@@ -392,10 +392,50 @@ pub enum TimeControl {
         black: u64,
         winc: u64,
         binc: u64,
-        movestogo: u64,
+        // None is sudden death
+        movestogo: Option<u64>,
     },
 }
 
+#[derive(Debug, Clone)]
+pub struct SearchLimits {
+    pub time_control: TimeControl,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    // mate in x moves, not plies
+    pub mate: Option<u32>,
+    pub search_moves: Vec<String>,
+    pub infinite: bool,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        SearchLimits {
+            time_control: TimeControl::Infinite,
+            depth: None,
+            nodes: None,
+            mate: None,
+            search_moves: Vec::new(),
+            infinite: false,
+        }
+    }
+}
+
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves",
+    "ponder",
+    "wtime",
+    "btime",
+    "winc",
+    "binc",
+    "movestogo",
+    "depth",
+    "nodes",
+    "mate",
+    "movetime",
+    "infinite",
+];
+
 #[derive(Debug, Clone)]
 pub enum RecceiveUCI {
     UCI,
@@ -412,18 +452,22 @@ pub enum RecceiveUCI {
     },
     Go {
         ponder: bool,
-        time_control: TimeControl,
-        depth: Option<u64>,
+        limits: SearchLimits,
     },
     Stop,
     PonderHit,
     Quit,
+    // not part of uci, prints the evaluation of the current position
+    Eval,
     Unknown(String),
 }
 
 impl RecceiveUCI {
     pub fn parse_str(s: &str) -> RecceiveUCI {
-        let keyword = s.split_whitespace().next().unwrap();
+        let keyword = match s.split_whitespace().next() {
+            Some(keyword) => keyword,
+            None => return RecceiveUCI::Unknown(s.to_string()),
+        };
         let parts = s.split_whitespace().skip(1).collect::<Vec<&str>>();
 
         match keyword {
@@ -431,6 +475,20 @@ impl RecceiveUCI {
             "debug" => RecceiveUCI::Debug(true),
             "isready" => RecceiveUCI::IsReady,
             "ucinewgame" => RecceiveUCI::UCINewGame,
+            "stop" => RecceiveUCI::Stop,
+            "ponderhit" => RecceiveUCI::PonderHit,
+            "quit" => RecceiveUCI::Quit,
+            "eval" => RecceiveUCI::Eval,
+            "setoption" => {
+                // option names and values can contain spaces
+                let name = parts.iter().position(|&s| s == "name").map_or(0, |i| i + 1);
+                let value = parts.iter().position(|&s| s == "value");
+                RecceiveUCI::SetOption {
+                    id: parts[name.min(parts.len())..value.unwrap_or(parts.len()).max(name)]
+                        .join(" "),
+                    value: value.map(|i| parts[i + 1..].join(" ")),
+                }
+            }
             "position" => {
                 let mut iter = s.split_whitespace().skip(1);
                 let position = match iter.next().unwrap() {
@@ -453,7 +511,27 @@ impl RecceiveUCI {
                 let depth = parts
                     .iter()
                     .position(|&s| s == "depth")
+                    .map(|i| parts[i + 1].parse::<u32>().unwrap());
+                let nodes = parts
+                    .iter()
+                    .position(|&s| s == "nodes")
                     .map(|i| parts[i + 1].parse::<u64>().unwrap());
+                let mate = parts
+                    .iter()
+                    .position(|&s| s == "mate")
+                    .map(|i| parts[i + 1].parse::<u32>().unwrap());
+                // all moves up to the next keyword
+                let search_moves = parts
+                    .iter()
+                    .position(|&s| s == "searchmoves")
+                    .map(|i| {
+                        parts[i + 1..]
+                            .iter()
+                            .take_while(|s| !GO_KEYWORDS.contains(s))
+                            .map(|s| s.to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default();
 
                 let time_control = if parts.contains(&"movetime") {
                     // get movetime
@@ -487,7 +565,7 @@ impl RecceiveUCI {
                         .iter()
                         .position(|&s| s == "movestogo")
                         .map(|i| parts[i + 1].parse::<u64>().unwrap())
-                        .unwrap_or(50);
+                        .filter(|&movestogo| movestogo > 0);
                     TimeControl::RemainingTime {
                         white: wtime,
                         black: btime,
@@ -500,8 +578,14 @@ impl RecceiveUCI {
                 };
                 RecceiveUCI::Go {
                     ponder,
-                    time_control,
-                    depth,
+                    limits: SearchLimits {
+                        time_control,
+                        depth,
+                        nodes,
+                        mate,
+                        search_moves,
+                        infinite: parts.contains(&"infinite"),
+                    },
                 }
             }
 
@@ -545,8 +629,61 @@ pub enum SendUCI {
     Id { name: String, author: String },
     UCIOk,
     ReadyOk,
-    BestMove { move1: Move, ponder: Option<Move> },
+    // without a move, in a position that is already mate or stalemate, "0000" is sent
+    BestMove { move1: Option<Move>, ponder: Option<Move> },
     Info(Info),
+    // several infos sent together, e.g. everything belonging to a pv
+    InfoLine(Vec<Info>),
+}
+
+impl From<crate::score::Score> for Score {
+    // the search counts mates in plies, uci in moves
+    fn from(score: crate::score::Score) -> Self {
+        match score {
+            crate::score::Score::CP(cp) => Score::CP(cp as i64),
+            crate::score::Score::MateIn(plies) if plies < 0 => Score::Mate((plies as i64 - 1) / 2),
+            crate::score::Score::MateIn(plies) => Score::Mate((plies as i64 + 1) / 2),
+        }
+    }
+}
+
+fn join_moves(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(|m| m.to_algebraic())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Info {
+    // without the leading "info", so several infos can share one line
+    pub fn to_str(&self) -> String {
+        match self {
+            Info::Depth(val) => format!("depth {}", val),
+            Info::SelDepth(val) => format!("seldepth {}", val),
+            Info::Time(val) => format!("time {}", val.as_millis()),
+            Info::Nodes(v) => format!("nodes {}", v),
+            Info::PV(v) => format!("pv {}", join_moves(v)),
+            Info::MultiPV(v) => format!("multipv {}", v),
+            Info::Score(v) => match v {
+                Score::CP(v) => format!("score cp {}", v),
+                Score::Mate(v) => format!("score mate {}", v),
+            },
+            Info::CurrMove(v) => format!("currmove {}", v.to_algebraic()),
+            Info::CurrMoveNumber(v) => format!("currmovenumber {}", v),
+            Info::HashFull(v) => format!("hashfull {}", v),
+            Info::NPS(v) => format!("nps {}", v),
+            Info::TBHits(v) => format!("tbhits {}", v),
+            Info::SBHits(v) => format!("sbhits {}", v),
+            Info::CPULoad(v) => format!("cpuload {}", v),
+            Info::String(v) => format!("string {}", v),
+            Info::Refutation(v) => format!("refutation {}", join_moves(v)),
+            Info::CurrLine { cpunr, moves } => {
+                let cpunr = cpunr.map(|v| v.to_string()).unwrap_or("".to_string());
+                format!("currline {} {}", cpunr, join_moves(moves))
+            }
+        }
+    }
 }
 
 impl SendUCI {
@@ -555,59 +692,104 @@ impl SendUCI {
             SendUCI::Id { name, author } => format!("id name {}\nid author {}", name, author),
             SendUCI::UCIOk => "uciok".to_string(),
             SendUCI::ReadyOk => "readyok".to_string(),
-            SendUCI::BestMove { move1, ponder } => match ponder {
-                Some(ponder) => format!(
-                    "bestmove {} ponder {}",
-                    move1.to_algebraic(),
-                    ponder.to_algebraic()
-                ),
-                None => format!("bestmove {}", move1.to_algebraic()),
-            },
-            SendUCI::Info(info) => match info {
-                Info::Depth(val) => format!("info depth {}", val),
-                Info::SelDepth(val) => format!("info seldepth {}", val),
-                Info::Time(val) => format!("info time {}", val.as_millis()),
-                Info::Nodes(v) => format!("info nodes {}", v),
-                Info::PV(v) => format!(
-                    "info pv {}",
-                    v.iter()
-                        .map(|m| m.to_algebraic())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                ),
-                Info::MultiPV(v) => format!("info multipv {}", v),
-                Info::Score(v) => match v {
-                    Score::CP(v) => format!("info score cp {}", v),
-                    Score::Mate(v) => format!("info score mate {}", v),
-                },
-                Info::CurrMove(v) => format!("info currmove {}", v.to_algebraic()),
-                Info::CurrMoveNumber(v) => format!("info currmovenumber {}", v),
-                Info::HashFull(v) => format!("info hashfull {}", v),
-                Info::NPS(v) => format!("info nps {}", v),
-                Info::TBHits(v) => format!("info tbhits {}", v),
-                Info::SBHits(v) => format!("info sbhits {}", v),
-                Info::CPULoad(v) => format!("info cpuload {}", v),
-                Info::String(v) => format!("info string {}", v),
-                Info::Refutation(v) => format!(
-                    "info refutation {}",
-                    v.iter()
-                        .map(|m| m.to_algebraic())
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                ),
-                Info::CurrLine { cpunr, moves } => {
-                    let cpunr = cpunr.map(|v| v.to_string()).unwrap_or("".to_string());
-                    format!(
-                        "info currline {} {}",
-                        cpunr,
-                        moves
-                            .iter()
-                            .map(|m| m.to_algebraic())
-                            .collect::<Vec<String>>()
-                            .join(" ")
-                    )
+            SendUCI::BestMove { move1, ponder } => {
+                let move1 = move1.map_or("0000".to_string(), |move1| move1.to_algebraic());
+                match ponder {
+                    Some(ponder) => format!("bestmove {} ponder {}", move1, ponder.to_algebraic()),
+                    None => format!("bestmove {}", move1),
                 }
+            }
+            SendUCI::Info(info) => format!("info {}", info.to_str()),
+            SendUCI::InfoLine(infos) => format!(
+                "info {}",
+                infos
+                    .iter()
+                    .map(|info| info.to_str())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setoption_with_spaces() {
+        match RecceiveUCI::parse_str("setoption name Move Overhead value 30\n") {
+            RecceiveUCI::SetOption { id, value } => {
+                assert_eq!(id, "Move Overhead");
+                assert_eq!(value.as_deref(), Some("30"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn go_limits() {
+        match RecceiveUCI::parse_str("go searchmoves e2e4 d2d4 depth 7 nodes 5000 mate 3\n") {
+            RecceiveUCI::Go { ponder, limits } => {
+                assert!(!ponder);
+                assert_eq!(limits.search_moves, vec!["e2e4", "d2d4"]);
+                assert_eq!(limits.depth, Some(7));
+                assert_eq!(limits.nodes, Some(5000));
+                assert_eq!(limits.mate, Some(3));
+                assert!(!limits.infinite);
+                assert!(matches!(limits.time_control, TimeControl::Infinite));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn go_clock() {
+        let movestogo = |command: &str| match RecceiveUCI::parse_str(command) {
+            RecceiveUCI::Go { limits, .. } => match limits.time_control {
+                TimeControl::RemainingTime { movestogo, .. } => movestogo,
+                other => panic!("unexpected {:?}", other),
             },
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(movestogo("go wtime 1000 btime 900 winc 10 binc 10\n"), None);
+        assert_eq!(movestogo("go wtime 1000 btime 900 movestogo 12\n"), Some(12));
+    }
+
+    #[test]
+    fn go_infinite() {
+        match RecceiveUCI::parse_str("go infinite\n") {
+            RecceiveUCI::Go { limits, .. } => {
+                assert!(limits.infinite);
+                assert!(limits.search_moves.is_empty());
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn info_line() {
+        let line = SendUCI::InfoLine(vec![
+            Info::Depth(3),
+            Info::Score(crate::score::Score::MateIn(3).into()),
+            Info::Nodes(120),
+            Info::PV(vec![Move::kingside_castle(true)]),
+        ]);
+        assert_eq!(line.to_str(), "info depth 3 score mate 2 nodes 120 pv e1g1");
+        assert_eq!(
+            SendUCI::Info(Info::Score(crate::score::Score::MateIn(-2).into())).to_str(),
+            "info score mate -1"
+        );
+    }
+
+    #[test]
+    fn setoption_without_value() {
+        match RecceiveUCI::parse_str("setoption name Clear Hash\n") {
+            RecceiveUCI::SetOption { id, value } => {
+                assert_eq!(id, "Clear Hash");
+                assert!(value.is_none());
+            }
+            other => panic!("unexpected {:?}", other),
         }
     }
 }
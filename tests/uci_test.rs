@@ -0,0 +1,390 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Engine {
+    fn start() -> Engine {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_gegene"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("failed to start engine");
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Engine {
+            child,
+            stdin,
+            lines,
+        }
+    }
+
+    fn send(&mut self, command: &str) {
+        writeln!(self.stdin, "{}", command).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    // collects all lines up to and including the first line starting with `prefix`
+    fn read_until(&self, prefix: &str, timeout: Duration) -> Vec<String> {
+        let deadline = Instant::now() + timeout;
+        let mut lines = Vec::new();
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let line = self
+                .lines
+                .recv_timeout(remaining)
+                .unwrap_or_else(|_| panic!("no `{}` within {:?}, got {:?}", prefix, timeout, lines));
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return lines;
+            }
+        }
+    }
+
+    fn wait_for_exit(&mut self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if self.child.try_wait().unwrap().is_some() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn count_bestmoves(lines: &[String]) -> usize {
+    lines.iter().filter(|line| line.starts_with("bestmove")).count()
+}
+
+#[test]
+fn uci_lists_options() {
+    let mut engine = Engine::start();
+    engine.send("uci");
+    let lines = engine.read_until("uciok", Duration::from_secs(5));
+
+    assert!(lines[0].starts_with("id name"));
+    assert!(lines.iter().any(|line| line.starts_with("option name Hash type spin")));
+}
+
+#[test]
+fn isready_is_answered_while_searching() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    engine.send("go infinite");
+    thread::sleep(Duration::from_millis(200));
+
+    engine.send("isready");
+    let lines = engine.read_until("readyok", Duration::from_secs(1));
+    assert_eq!(count_bestmoves(&lines), 0);
+
+    engine.send("stop");
+    let lines = engine.read_until("bestmove", Duration::from_secs(5));
+    assert_eq!(count_bestmoves(&lines), 1);
+
+    engine.send("isready");
+    let lines = engine.read_until("readyok", Duration::from_secs(5));
+    assert_eq!(count_bestmoves(&lines), 0);
+}
+
+#[test]
+fn first_iteration_finishes_without_time() {
+    // less time than the default move overhead
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go movetime 5");
+    let lines = engine.read_until("bestmove", Duration::from_secs(5));
+    assert!(lines.iter().any(|line| line.starts_with("info depth 1 ")));
+}
+
+#[test]
+fn one_bestmove_per_go() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go movetime 100");
+    engine.send("go movetime 100");
+    engine.send("isready");
+    let mut lines = engine.read_until("readyok", Duration::from_secs(5));
+
+    // the second go was started after the first was stopped, wait for it to finish too
+    if count_bestmoves(&lines) < 2 {
+        lines.extend(engine.read_until("bestmove", Duration::from_secs(5)));
+    }
+    engine.send("isready");
+    lines.extend(engine.read_until("readyok", Duration::from_secs(5)));
+
+    assert_eq!(count_bestmoves(&lines), 2);
+}
+
+#[test]
+fn quit_during_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go infinite");
+    thread::sleep(Duration::from_millis(100));
+    engine.send("quit");
+
+    assert!(engine.wait_for_exit(Duration::from_secs(5)));
+}
+
+#[test]
+fn ponder_waits_for_ponderhit() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4 e7e5");
+    engine.send("go ponder wtime 1000 btime 1000");
+    thread::sleep(Duration::from_millis(500));
+
+    // the clock must not run out while pondering
+    engine.send("isready");
+    let lines = engine.read_until("readyok", Duration::from_secs(1));
+    assert_eq!(count_bestmoves(&lines), 0);
+
+    engine.send("ponderhit");
+    let lines = engine.read_until("bestmove", Duration::from_secs(5));
+    let bestmove = lines.last().unwrap();
+    assert!(bestmove.contains(" ponder "), "{}", bestmove);
+}
+
+#[test]
+fn stop_ends_ponder_search() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    engine.send("go ponder movetime 100");
+    thread::sleep(Duration::from_millis(300));
+
+    engine.send("stop");
+    let lines = engine.read_until("bestmove", Duration::from_secs(5));
+    assert_eq!(count_bestmoves(&lines), 1);
+}
+
+#[test]
+fn fixed_depth_and_nodes_finish_on_their_own() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    engine.send("go depth 2");
+    let lines = engine.read_until("bestmove", Duration::from_secs(10));
+    assert_eq!(count_bestmoves(&lines), 1);
+
+    // the same node limit has to give the same move
+    engine.send("ucinewgame");
+    engine.send("go nodes 2000");
+    let first = engine.read_until("bestmove", Duration::from_secs(10));
+    engine.send("ucinewgame");
+    engine.send("go nodes 2000");
+    let second = engine.read_until("bestmove", Duration::from_secs(10));
+    assert_eq!(first.last(), second.last());
+}
+
+#[test]
+fn unfinished_iterations_are_not_reported() {
+    // the first root move can't even be finished, no score is known yet
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go nodes 1");
+    let lines = engine.read_until("bestmove", Duration::from_secs(10));
+    assert_eq!(count_bestmoves(&lines), 1);
+    assert!(lines.iter().all(|line| !line.starts_with("info depth")), "{:?}", lines);
+}
+
+#[test]
+fn no_legal_move_is_a_null_bestmove() {
+    let mut engine = Engine::start();
+    // fool's mate, then a stalemate
+    for fen in [
+        "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3",
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+    ] {
+        engine.send(&format!("position fen {}", fen));
+        engine.send("go depth 5");
+        let lines = engine.read_until("bestmove", Duration::from_secs(10));
+        assert_eq!(lines.last().map(String::as_str), Some("bestmove 0000"), "{}", fen);
+    }
+}
+
+#[test]
+fn searchmoves_restricts_the_root() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go depth 3 searchmoves a2a3 h2h3");
+    let lines = engine.read_until("bestmove", Duration::from_secs(10));
+    let bestmove = lines.last().unwrap();
+    assert!(
+        bestmove.starts_with("bestmove a2a3") || bestmove.starts_with("bestmove h2h3"),
+        "{}",
+        bestmove
+    );
+}
+
+#[test]
+fn info_line_before_bestmove() {
+    let mut engine = Engine::start();
+    engine.send("position startpos");
+    engine.send("go depth 3");
+    let lines = engine.read_until("bestmove", Duration::from_secs(10));
+
+    let info = &lines[lines.len() - 2];
+    assert!(info.starts_with("info depth 3 seldepth "), "{}", info);
+    for field in [" score cp ", " nodes ", " nps ", " hashfull ", " time ", " pv "] {
+        assert!(info.contains(field), "{} missing in {}", field, info);
+    }
+    let bestmove = lines.last().unwrap().split_whitespace().nth(1).unwrap();
+    assert_eq!(info.split(" pv ").nth(1).unwrap().split_whitespace().next(), Some(bestmove));
+}
+
+#[test]
+fn multipv_reports_lines_in_rank_order() {
+    let mut engine = Engine::start();
+    engine.send("setoption name MultiPV value 3");
+    engine.send("position startpos");
+    engine.send("go depth 2");
+    let lines = engine.read_until("bestmove", Duration::from_secs(10));
+
+    let last_depth: Vec<&String> = lines
+        .iter()
+        .filter(|line| line.starts_with("info depth 2 "))
+        .collect();
+    assert_eq!(last_depth.len(), 3);
+    for (i, line) in last_depth.iter().enumerate() {
+        assert!(line.contains(&format!(" multipv {} ", i + 1)), "{}", line);
+    }
+
+    // the first line has to be the move that is played
+    let bestmove = lines.last().unwrap().split_whitespace().nth(1).unwrap();
+    assert!(last_depth[0].contains(&format!(" pv {}", bestmove)));
+}
+
+#[test]
+fn eval_prints_every_term() {
+    let mut engine = Engine::start();
+    engine.send("position startpos moves e2e4");
+    engine.send("eval");
+    let lines = engine.read_until("Final evaluation", Duration::from_secs(5));
+
+    for term in ["Material", "PST", "Pawns", "Mobility", "King safety", "Total"] {
+        assert!(lines.iter().any(|line| line.trim_start().starts_with(term)), "{} missing", term);
+    }
+    assert!(lines.last().unwrap().ends_with("cp (white side)"));
+}
+
+// the score of the last info line before bestmove
+fn final_score(lines: &[String]) -> String {
+    let info = lines
+        .iter()
+        .rev()
+        .find(|line| line.starts_with("info depth"))
+        .expect("no info line");
+    let score = info.split(" score ").nth(1).unwrap();
+    score.split_whitespace().take(2).collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn reports_mate_distances() {
+    // Légal-like king hunt: Bc5+ Kxc5 Qb6+ Kd5 Qd6#
+    let hunt = "r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1";
+    let positions = [
+        ("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", "mate 1"),
+        // Morphy, Ra6 bxa6 b7#
+        ("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", "mate 2"),
+        ("kbK5/pp6/RP6/8/8/8/8/8 b - - 1 1", "mate -1"),
+        (hunt, "mate 3"),
+    ];
+
+    let mut engine = Engine::start();
+    for (fen, expected) in positions {
+        engine.send("ucinewgame");
+        engine.send(&format!("position fen {}", fen));
+        engine.send("go depth 6");
+        let lines = engine.read_until("bestmove", Duration::from_secs(30));
+        assert_eq!(final_score(&lines), expected, "{}", fen);
+    }
+
+    // the table keeps the entries of the earlier searches, found at other plies
+    for (moves, expected) in [("f8c5 d4c5", "mate 2"), ("f8c5 d4c5 f6b6 c5d5", "mate 1")] {
+        engine.send(&format!("position fen {} moves {}", hunt, moves));
+        engine.send("go depth 6");
+        let lines = engine.read_until("bestmove", Duration::from_secs(30));
+        assert_eq!(final_score(&lines), expected, "{}", moves);
+    }
+}
+
+#[test]
+fn checks_are_followed_past_the_horizon() {
+    // the king hunt needs five plies, all of them checks or forced replies
+    let mut engine = Engine::start();
+    engine.send("position fen r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1");
+    engine.send("go depth 4");
+    let lines = engine.read_until("bestmove", Duration::from_secs(30));
+    assert_eq!(final_score(&lines), "mate 3");
+
+    let info = lines.iter().rev().find(|line| line.starts_with("info depth 4 ")).unwrap();
+    let seldepth: u32 = info.split(" seldepth ").nth(1).unwrap().split_whitespace().next().unwrap().parse().unwrap();
+    assert!(seldepth > 4, "{}", info);
+}
+
+#[test]
+fn perpetual_check_is_a_draw() {
+    // a queen down, white checks from e8 and h5 forever
+    let mut engine = Engine::start();
+    engine.send("position fen 6k1/6p1/8/8/8/1r6/1q2Q1PP/7K w - - 0 1");
+    engine.send("go depth 8");
+    let lines = engine.read_until("bestmove", Duration::from_secs(30));
+    assert_eq!(final_score(&lines), "cp 10");
+    assert!(lines.last().unwrap().starts_with("bestmove e2e8"));
+}
+
+#[test]
+fn wrong_bishop_is_a_draw() {
+    // the bishop can't cover h8, the king can't be driven out of the corner
+    let mut engine = Engine::start();
+    engine.send("position fen 7k/8/8/8/8/3K3P/8/5B2 w - - 0 1");
+    engine.send("go depth 8");
+    let lines = engine.read_until("bestmove", Duration::from_secs(30));
+    let score: i32 = final_score(&lines)
+        .strip_prefix("cp ")
+        .and_then(|cp| cp.parse().ok())
+        .expect("no centipawn score");
+    assert!(score < 50, "{}", score);
+}
+
+#[test]
+fn helper_threads_share_the_search() {
+    let mut engine = Engine::start();
+    engine.send("setoption name Threads value 4");
+    engine.send("position startpos");
+    engine.send("go depth 5");
+    let lines = engine.read_until("bestmove", Duration::from_secs(30));
+    assert_eq!(count_bestmoves(&lines), 1);
+
+    // only the main thread reports, one line per depth
+    for depth in 1..=5 {
+        let prefix = format!("info depth {} ", depth);
+        assert_eq!(lines.iter().filter(|line| line.starts_with(&prefix)).count(), 1);
+    }
+}